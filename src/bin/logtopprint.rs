@@ -12,6 +12,10 @@ struct Cli {
     /// Path to the SQLite database file
     #[arg(short, long, default_value = "queries.db")]
     database: String,
+    /// Input the query was loaded from, when several captures share the
+    /// database
+    #[arg(short, long)]
+    source_file: Option<String>,
 }
 
 fn main() -> Result<()> {
@@ -22,11 +26,18 @@ fn main() -> Result<()> {
         .with_context(|| format!("Failed to open database: {}", cli.database))?;
 
     // Query the log entry
-    let mut stmt = conn.prepare("SELECT replaced_query FROM logs WHERE query_no = ? LIMIT 1")?;
-    let query: Option<String> = stmt.query_row([&cli.query_no], |row| row.get(0)).ok();
+    let mut stmt = conn.prepare(
+        "SELECT replaced_query, source_file FROM logs
+        WHERE query_no = ?1 AND (?2 IS NULL OR source_file = ?2) LIMIT 1",
+    )?;
+    let query: Option<(String, String)> = stmt
+        .query_row((&cli.query_no, &cli.source_file), |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .ok();
 
     match query {
-        Some(sql) => {
+        Some((sql, source_file)) => {
             // Create formatting options
             let options = FormatOptions {
                 indent: sqlformat::Indent::Spaces(4),
@@ -39,8 +50,31 @@ fn main() -> Result<()> {
             // Note: The order matters! query -> params -> options
             let formatted = sqlformat::format(&sql, &QueryParams::None, &options);
 
-            println!("Query #{}\n", cli.query_no);
+            println!("Query #{} ({})\n", cli.query_no, source_file);
             println!("{}", formatted);
+
+            // Statistics are only present when the log_top.res of the same
+            // capture has been imported.
+            let stats = conn
+                .query_row(
+                    "SELECT max_time, min_time, avg_time, exec_count, error_count
+                    FROM query_stats WHERE capture = ?1 AND query_no = ?2 LIMIT 1",
+                    [&source_file, &cli.query_no],
+                    |row| {
+                        Ok((
+                            row.get::<_, f64>(0)?,
+                            row.get::<_, f64>(1)?,
+                            row.get::<_, f64>(2)?,
+                            row.get::<_, i64>(3)?,
+                            row.get::<_, i64>(4)?,
+                        ))
+                    },
+                )
+                .ok();
+            if let Some((max, min, avg, count, errors)) = stats {
+                println!();
+                println!("max: {max:.3}  min: {min:.3}  avg: {avg:.3}  executions: {count} ({errors} errors)");
+            }
         }
         None => {
             println!("No query found with number: {}", cli.query_no);
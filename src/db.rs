@@ -1,13 +1,79 @@
-use crate::parser::LogEntry;
+use crate::parser::{
+    find_placeholders, AccessLogEntry, Diagnostic, LogEntry, QueryStats, Transaction,
+};
 use crate::utils::query_format::adhoc_fix_query;
-use anyhow::Result;
-use indicatif::{ProgressBar, ProgressStyle};
+use anyhow::{bail, Result};
 use rusqlite::{params, Connection};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+/// Number of entries inserted per transaction.
+pub const BATCH_SIZE: usize = 10_000;
+
+/// Version of the schema, stored in `PRAGMA user_version`. Bump it with
+/// every column added to an existing table, and list the column in
+/// [`ADDED_COLUMNS`].
+const SCHEMA_VERSION: i32 = 1;
+
+/// Columns added to tables that databases written by earlier releases
+/// already have, with the definition used to add them there. A column that
+/// is `NOT NULL` needs a default for `ALTER TABLE ... ADD COLUMN`.
+const ADDED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("logs", "source_file", "TEXT NOT NULL DEFAULT ''"),
+    ("logs", "bind_types", "JSON NOT NULL DEFAULT '[]'"),
+    ("logs", "start_time", "TEXT"),
+    ("logs", "end_time", "TEXT"),
+    ("logs", "pid", "INTEGER"),
+    ("logs", "error_code", "INTEGER"),
+    ("logs", "tuple_count", "INTEGER"),
+    ("logs", "elapsed", "REAL"),
+    ("logs", "srv_h_id", "INTEGER"),
+    ("logs", "source_line", "INTEGER"),
+    ("logs", "broker_name", "TEXT"),
+    ("logs", "cas_id", "INTEGER"),
+    ("logs", "parse_error", "TEXT"),
+    ("logs", "transaction_no", "INTEGER"),
+    (
+        "logs",
+        "transaction_id",
+        "INTEGER REFERENCES transactions(id)",
+    ),
+    ("logs", "bind_status", "TEXT NOT NULL DEFAULT 'ok'"),
+    ("logs", "mismatch_cause", "TEXT"),
+    ("logs", "execute_kind", "TEXT NOT NULL DEFAULT 'execute'"),
+    ("logs", "batch_size", "INTEGER"),
+];
 
 pub struct Database {
     conn: Connection,
 }
 
+/// Rows of the `transactions` table written for one input, by transaction
+/// number, so that executions are linked to the transactions of their own
+/// input even when the same file is loaded more than once.
+///
+/// Only the transactions that executions still to come may belong to are
+/// kept.
+#[derive(Debug, Default)]
+pub struct TransactionRows {
+    /// Row of each transaction, and whether it has closed.
+    rows: BTreeMap<u64, (i64, bool)>,
+    /// Transaction of the last execution inserted.
+    last_execution: Option<u64>,
+}
+
+impl TransactionRows {
+    /// Forgets the transactions that have closed and that no execution still
+    /// to come belongs to: executions come in the order of their
+    /// transactions.
+    fn prune(&mut self) {
+        if let Some(last) = self.last_execution {
+            self.rows
+                .retain(|&transaction_no, &mut (_, closed)| !closed || transaction_no >= last);
+        }
+    }
+}
+
 impl Database {
     pub fn new(db_path: &str) -> Result<Self> {
         let conn = Connection::open(db_path)?;
@@ -18,89 +84,849 @@ impl Database {
         self.conn.execute(
             "CREATE TABLE IF NOT EXISTS logs (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
+                source_file TEXT NOT NULL,
                 query_no TEXT NOT NULL,
                 filename TEXT NOT NULL,
                 original_query TEXT NOT NULL,
                 replaced_query TEXT,
+                bind_vars JSON NOT NULL,
+                bind_types JSON NOT NULL,
+                start_time TEXT,
+                end_time TEXT,
+                pid INTEGER,
+                error_code INTEGER,
+                tuple_count INTEGER,
+                elapsed REAL,
+                srv_h_id INTEGER,
+                source_line INTEGER,
+                broker_name TEXT,
+                cas_id INTEGER,
+                parse_error TEXT,
+                transaction_no INTEGER,
+                transaction_id INTEGER REFERENCES transactions(id),
+                bind_status TEXT NOT NULL DEFAULT 'ok',
+                mismatch_cause TEXT,
+                execute_kind TEXT NOT NULL DEFAULT 'execute',
+                batch_size INTEGER
+            )",
+            [],
+        )?;
+        // One row per parameter set of a batch execution, rebound separately.
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS batch_statements (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                log_id INTEGER NOT NULL REFERENCES logs(id),
+                set_no INTEGER NOT NULL,
+                replaced_query TEXT NOT NULL,
                 bind_vars JSON NOT NULL
             )",
             [],
         )?;
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS batch_statements_by_log
+            ON batch_statements (log_id, set_no)",
+            [],
+        )?;
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS diagnostics (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                source_file TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                line_no INTEGER NOT NULL,
+                byte_offset INTEGER NOT NULL,
+                query_no TEXT,
+                text TEXT NOT NULL,
+                message TEXT NOT NULL
+            )",
+            [],
+        )?;
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS access_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                source_file TEXT NOT NULL,
+                source_line INTEGER NOT NULL,
+                broker_name TEXT,
+                cas_id INTEGER NOT NULL,
+                client_ip TEXT NOT NULL,
+                client_app TEXT,
+                start_time TEXT NOT NULL,
+                end_time TEXT NOT NULL,
+                elapsed REAL NOT NULL,
+                pid INTEGER NOT NULL,
+                error_message TEXT,
+                error_code INTEGER
+            )",
+            [],
+        )?;
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS query_stats (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                source_file TEXT NOT NULL,
+                capture TEXT,
+                query_no TEXT NOT NULL,
+                max_time REAL NOT NULL,
+                min_time REAL NOT NULL,
+                avg_time REAL NOT NULL,
+                exec_count INTEGER NOT NULL,
+                error_count INTEGER NOT NULL
+            )",
+            [],
+        )?;
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS transactions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                source_file TEXT NOT NULL,
+                transaction_no INTEGER NOT NULL,
+                broker_name TEXT,
+                cas_id INTEGER,
+                start_time TEXT,
+                end_time TEXT,
+                elapsed REAL,
+                outcome TEXT,
+                execution_count INTEGER NOT NULL
+            )",
+            [],
+        )?;
+        self.conn.execute(
+            "CREATE INDEX IF NOT EXISTS transactions_by_no
+            ON transactions (source_file, transaction_no)",
+            [],
+        )?;
+        self.migrate()?;
+
+        // Links every execution to the client session that was open on the
+        // same CAS when it started.
+        self.conn.execute(
+            "CREATE VIEW IF NOT EXISTS log_sessions AS
+            SELECT logs.id AS log_id, logs.query_no, logs.broker_name, logs.cas_id,
+                logs.start_time, logs.elapsed, logs.replaced_query,
+                access_log.id AS session_id, access_log.client_ip, access_log.client_app,
+                access_log.start_time AS session_start, access_log.end_time AS session_end
+            FROM logs
+            JOIN access_log ON access_log.cas_id = logs.cas_id
+                AND (access_log.broker_name IS NULL OR logs.broker_name IS NULL
+                    OR access_log.broker_name = logs.broker_name)
+                AND julianday(logs.start_time)
+                    BETWEEN julianday(access_log.start_time) AND julianday(access_log.end_time)",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// Brings the tables of a database written by an earlier release up to
+    /// [`SCHEMA_VERSION`] by adding the columns they lack.
+    fn migrate(&self) -> Result<()> {
+        let version: i32 = self
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            bail!(
+                "The database was written by a newer release (schema version {}, expected {}): \
+                remove it to start a new one",
+                version,
+                SCHEMA_VERSION
+            );
+        }
+        if version == SCHEMA_VERSION {
+            return Ok(());
+        }
+
+        for (table, column, definition) in ADDED_COLUMNS {
+            let exists = self
+                .conn
+                .prepare(&format!("PRAGMA table_info({})", table))?
+                .query_map([], |row| row.get::<_, String>(1))?
+                .collect::<rusqlite::Result<Vec<_>>>()?
+                .iter()
+                .any(|name| name == column);
+            if !exists {
+                self.conn.execute(
+                    &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+                    [],
+                )?;
+            }
+        }
+        self.conn
+            .pragma_update(None, "user_version", SCHEMA_VERSION)?;
         Ok(())
     }
 
-    pub fn process_entries(&mut self, entries: &[LogEntry]) -> Result<()> {
-        let progress_bar = self.create_progress_bar(entries.len());
-        let mut prepared_entries = Vec::with_capacity(entries.len());
+    /// Inserts entries into the `logs` table as they are produced.
+    ///
+    /// Entries are committed in batches of [`BATCH_SIZE`] so that neither the
+    /// parsed entries nor a single huge transaction have to be held at once.
+    /// `source_file` names the input the entries were read from. Entries
+    /// that belong to a transaction are linked to its row in `transactions`,
+    /// which is written with the first of them if it is not yet in
+    /// `transactions` and completed by
+    /// [`insert_transactions`](Self::insert_transactions). Returns the number
+    /// of inserted rows.
+    pub fn process_entries<I>(
+        &mut self,
+        source_file: &str,
+        entries: I,
+        transactions: &mut TransactionRows,
+    ) -> Result<usize>
+    where
+        I: IntoIterator<Item = Result<LogEntry>>,
+    {
+        let mut entries = entries.into_iter().peekable();
+        let mut inserted = 0;
+
+        while entries.peek().is_some() {
+            let tx = self.conn.transaction()?;
+            {
+                let mut stmt = tx.prepare_cached(
+                    "INSERT INTO logs (source_file, query_no, filename, original_query, replaced_query, bind_vars,
+                        bind_types, start_time, end_time, pid, error_code, tuple_count, elapsed,
+                        srv_h_id, source_line, broker_name, cas_id, parse_error, transaction_no,
+                        transaction_id, bind_status, mismatch_cause, execute_kind, batch_size) 
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17,
+                        ?18, ?19, ?20, ?21, ?22, ?23, ?24)",
+                )?;
+                let mut transaction_stmt = tx.prepare_cached(
+                    "INSERT INTO transactions (source_file, transaction_no, broker_name, cas_id,
+                        start_time, execution_count)
+                    VALUES (?1, ?2, ?3, ?4, ?5, 0)",
+                )?;
+                let mut batch_stmt = tx.prepare_cached(
+                    "INSERT INTO batch_statements (log_id, set_no, replaced_query, bind_vars)
+                    VALUES (?1, ?2, ?3, ?4)",
+                )?;
+
+                for entry in entries.by_ref().take(BATCH_SIZE) {
+                    let entry = entry?;
+
+                    // A batch execution of several parameter sets shows the
+                    // first one, every set going to `batch_statements`; a
+                    // single set is an ordinary execution. Otherwise bind as
+                    // many placeholders as possible, even when the counts do
+                    // not match.
+                    let placeholders = find_placeholders(&entry.query);
+                    let batch = entry
+                        .parameter_sets_with(&placeholders)
+                        .filter(|sets| sets.len() > 1);
+                    let replaced_query = match batch.as_deref() {
+                        Some([first, ..]) => {
+                            LogEntry::rebind_with(&entry.query, &placeholders, first)
+                        }
+                        _ => LogEntry::rebind_with(
+                            &entry.query,
+                            &placeholders,
+                            &entry.bind_statements,
+                        ),
+                    };
+                    let mismatch = entry.bind_mismatch_with(&placeholders);
+                    let bind_status = match (&mismatch, &batch) {
+                        (Some(_), _) => "mismatch",
+                        (None, Some(_)) => "batch",
+                        (None, None) => "ok",
+                    };
+
+                    let fixed_query = adhoc_fix_query(&replaced_query);
+
+                    // Convert bind values and their declared types to JSON
+                    let bind_values: Vec<&str> = entry
+                        .bind_statements
+                        .iter()
+                        .map(|b| b.value.as_str())
+                        .collect();
+                    let bind_statements_json = serde_json::to_string(&bind_values)
+                        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+                    let bind_types: Vec<serde_json::Value> = entry
+                        .bind_statements
+                        .iter()
+                        .map(|b| {
+                            json!({
+                                "type": b.bind_type.name(),
+                                "length": b.length,
+                                "truncated": b.truncated,
+                            })
+                        })
+                        .collect();
+                    let bind_types_json = serde_json::to_string(&bind_types)
+                        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+                    let transaction_id = match entry.transaction_no {
+                        Some(transaction_no) => {
+                            transactions.last_execution = Some(transaction_no);
+                            transactions.prune();
+                            let row = match transactions.rows.get(&transaction_no) {
+                                Some(&(row, _)) => row,
+                                None => {
+                                    let row = transaction_stmt.insert(params![
+                                        source_file,
+                                        transaction_no,
+                                        &entry.broker_name,
+                                        &entry.cas_id,
+                                        &entry.start_time,
+                                    ])?;
+                                    transactions.rows.insert(transaction_no, (row, false));
+                                    row
+                                }
+                            };
+                            Some(row)
+                        }
+                        None => None,
+                    };
 
-        for entry in entries {
-            // Try to replace query parameters
-            let replaced_query =
-                match LogEntry::replace_query_params(&entry.query, &entry.bind_statements) {
-                    Ok(replaced) => replaced,
-                    Err(e) => {
-                        eprintln!("Error processing query {}: {}", entry.query_no, e);
-                        String::new() // Empty string for failed replacements
+                    let log_id = stmt.insert(params![
+                        source_file,
+                        &entry.query_no,
+                        &entry.filename,
+                        &entry.query,
+                        &fixed_query,
+                        &bind_statements_json,
+                        &bind_types_json,
+                        &entry.start_time,
+                        &entry.end_time,
+                        &entry.pid,
+                        &entry.error_code,
+                        &entry.tuple_count,
+                        &entry.elapsed,
+                        &entry.srv_h_id,
+                        &entry.source_line,
+                        &entry.broker_name,
+                        &entry.cas_id,
+                        &entry.parse_error,
+                        &entry.transaction_no,
+                        transaction_id,
+                        bind_status,
+                        mismatch.map(|cause| cause.name()),
+                        entry.execute_kind.name(),
+                        batch.as_ref().map(Vec::len),
+                    ])?;
+                    inserted += 1;
+
+                    for (set_no, set) in batch.iter().flatten().enumerate() {
+                        let replaced_query = adhoc_fix_query(&LogEntry::rebind_with(
+                            &entry.query,
+                            &placeholders,
+                            set,
+                        ));
+                        let bind_values: Vec<&str> = set.iter().map(|b| b.value.as_str()).collect();
+                        let bind_vars_json = serde_json::to_string(&bind_values)
+                            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+                        batch_stmt.execute(params![
+                            log_id,
+                            set_no + 1,
+                            &replaced_query,
+                            &bind_vars_json,
+                        ])?;
                     }
-                };
-
-            let fixed_query = adhoc_fix_query(&replaced_query);
-
-            // Convert bind statements to JSON
-            let bind_statements_json = serde_json::to_string(&entry.bind_statements)
-                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
-
-            prepared_entries.push((
-                entry.query_no.clone(),
-                entry.filename.clone(),
-                entry.query.clone(),
-                fixed_query,
-                bind_statements_json,
-            ));
-            progress_bar.inc(1);
+                }
+            }
+            tx.commit()?;
         }
-        progress_bar.finish_with_message("All log entries processed successfully!");
 
-        println!("Inserting log entries into database...");
-        let progress_bar = self.create_progress_bar(entries.len());
+        Ok(inserted)
+    }
+
+    /// Inserts the diagnostics reported while parsing `source_file`.
+    pub fn insert_diagnostics(
+        &mut self,
+        source_file: &str,
+        diagnostics: &[Diagnostic],
+    ) -> Result<()> {
         let tx = self.conn.transaction()?;
         {
             let mut stmt = tx.prepare_cached(
-                "INSERT INTO logs (query_no, filename, original_query, replaced_query, bind_vars) 
-                VALUES (?1, ?2, ?3, ?4, ?5)",
+                "INSERT INTO diagnostics (source_file, kind, line_no, byte_offset, query_no, text, message) 
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
             )?;
 
-            for (query_no, filename, query, formatted_query, bind_statements_json) in
-                prepared_entries
-            {
-                // Insert into database
+            for diagnostic in diagnostics {
                 stmt.execute(params![
-                    &query_no,
-                    &filename,
-                    &query,
-                    &formatted_query,
-                    &bind_statements_json,
+                    source_file,
+                    diagnostic.kind.name(),
+                    diagnostic.line_no,
+                    diagnostic.byte_offset,
+                    &diagnostic.query_no,
+                    &diagnostic.text,
+                    &diagnostic.message,
                 ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Inserts the sessions of the access log `source_file` into the
+    /// `access_log` table, in batches of [`BATCH_SIZE`]. Returns the number of
+    /// inserted rows.
+    pub fn insert_access_log<I>(&mut self, source_file: &str, entries: I) -> Result<usize>
+    where
+        I: IntoIterator<Item = Result<AccessLogEntry>>,
+    {
+        let mut entries = entries.into_iter().peekable();
+        let mut inserted = 0;
+
+        while entries.peek().is_some() {
+            let tx = self.conn.transaction()?;
+            {
+                let mut stmt = tx.prepare_cached(
+                    "INSERT INTO access_log (source_file, source_line, broker_name, cas_id, client_ip,
+                        client_app, start_time, end_time, elapsed, pid, error_message, error_code)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                )?;
+
+                for entry in entries.by_ref().take(BATCH_SIZE) {
+                    let entry = entry?;
+                    stmt.execute(params![
+                        source_file,
+                        entry.source_line,
+                        &entry.broker_name,
+                        entry.cas_id,
+                        &entry.client_ip,
+                        &entry.client_app,
+                        &entry.start_time,
+                        &entry.end_time,
+                        entry.elapsed(),
+                        entry.pid,
+                        &entry.error_message,
+                        &entry.error_code,
+                    ])?;
+                    inserted += 1;
+                }
+            }
+            tx.commit()?;
+        }
+
+        Ok(inserted)
+    }
+
+    /// Writes the transactions of `source_file` that have closed. The row of
+    /// a transaction whose executions were already inserted is completed,
+    /// the others are inserted and recorded in `rows` for the executions
+    /// still to come.
+    pub fn insert_transactions(
+        &mut self,
+        source_file: &str,
+        transactions: &[Transaction],
+        rows: &mut TransactionRows,
+    ) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut insert_stmt = tx.prepare_cached(
+                "INSERT INTO transactions (source_file, transaction_no, broker_name, cas_id,
+                    start_time, end_time, elapsed, outcome, execution_count)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )?;
+            let mut update_stmt = tx.prepare_cached(
+                "UPDATE transactions SET broker_name = ?2, cas_id = ?3, start_time = ?4,
+                    end_time = ?5, elapsed = ?6, outcome = ?7, execution_count = ?8
+                WHERE id = ?1",
+            )?;
+
+            for transaction in transactions {
+                let outcome = transaction.outcome.map(|outcome| outcome.name());
+                match rows.rows.get_mut(&transaction.transaction_no) {
+                    Some((row, closed)) => {
+                        *closed = true;
+                        update_stmt.execute(params![
+                            *row,
+                            &transaction.broker_name,
+                            &transaction.cas_id,
+                            &transaction.start_time,
+                            &transaction.end_time,
+                            transaction.elapsed(),
+                            outcome,
+                            transaction.execution_count,
+                        ])?;
+                    }
+                    None => {
+                        let row = insert_stmt.insert(params![
+                            source_file,
+                            transaction.transaction_no,
+                            &transaction.broker_name,
+                            &transaction.cas_id,
+                            &transaction.start_time,
+                            &transaction.end_time,
+                            transaction.elapsed(),
+                            outcome,
+                            transaction.execution_count,
+                        ])?;
+                        rows.rows.insert(transaction.transaction_no, (row, true));
+                    }
+                }
+            }
+            rows.prune();
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Inserts the per-query statistics of the `log_top.res` file
+    /// `source_file` into the `query_stats` table. `capture` is the
+    /// `source_file` of the `log_top.q` rows of `logs` the statistics belong
+    /// to, with which they share their query numbers. Returns the number of
+    /// inserted rows.
+    pub fn insert_query_stats<I>(
+        &mut self,
+        source_file: &str,
+        capture: Option<&str>,
+        stats: I,
+    ) -> Result<usize>
+    where
+        I: IntoIterator<Item = Result<QueryStats>>,
+    {
+        let mut inserted = 0;
+        let tx = self.conn.transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO query_stats (source_file, capture, query_no, max_time, min_time,
+                    avg_time, exec_count, error_count)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            )?;
 
-                progress_bar.inc(1);
+            for stats in stats {
+                let stats = stats?;
+                stmt.execute(params![
+                    source_file,
+                    capture,
+                    &stats.query_no,
+                    stats.max_time,
+                    stats.min_time,
+                    stats.avg_time,
+                    stats.exec_count,
+                    stats.error_count,
+                ])?;
+                inserted += 1;
             }
         }
         tx.commit()?;
+        Ok(inserted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{AccessLogEntries, EntryStream, LogEntries, ResEntries, SqlLogEntries};
+
+    #[test]
+    fn test_log_sessions() -> Result<()> {
+        let log = "\
+[Q1]-------------------------------------------
+broker1_2.sql.log:5
+21-02-24 15:30:45.123 (1) execute srv_h_id 1 SELECT 1
+21-02-24 15:30:45.124 (1) execute 0 tuple 1 time 0.001
+";
+        let access = "\
+2 10.0.0.1 - 21/02/24 15:30:40 ~ 21/02/24 15:30:50 100 - -1
+2 10.0.0.2 - 21/02/24 15:30:51 ~ 21/02/24 15:30:59 100 - -1
+3 10.0.0.3 - 21/02/24 15:30:40 ~ 21/02/24 15:30:50 101 - -1
+";
+        let mut db = Database::new(":memory:")?;
+        db.initialize()?;
+        db.process_entries(
+            "log_top.q",
+            LogEntries::new(log.as_bytes()),
+            &mut TransactionRows::default(),
+        )?;
+        db.insert_access_log(
+            "broker1.access",
+            AccessLogEntries::new(access.as_bytes()).filename("broker1.access"),
+        )?;
 
-        progress_bar.finish_with_message("All log entries processed successfully!");
+        let client_ips = db
+            .conn
+            .prepare("SELECT client_ip FROM log_sessions WHERE query_no = '1'")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        assert_eq!(client_ips, vec!["10.0.0.1"]);
         Ok(())
     }
 
-    fn create_progress_bar(&self, total_entries: usize) -> ProgressBar {
-        let pb = ProgressBar::new(total_entries as u64);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template(
-                    "{spinner:.green} [{bar:40.cyan/blue}] {pos}/{len} ({percent}%) - ETA: {eta}",
-                )
-                .unwrap()
-                .progress_chars("#>-"),
+    #[test]
+    fn test_transactions() -> Result<()> {
+        let log = "\
+21-02-24 15:30:45.112 (1) execute srv_h_id 1 INSERT INTO t VALUES (1)
+21-02-24 15:30:45.113 (1) execute 0 tuple 1 time 0.001
+21-02-24 15:30:45.114 (1) execute srv_h_id 1 INSERT INTO t VALUES (2)
+21-02-24 15:30:45.116 (1) execute 0 tuple 1 time 0.002
+21-02-24 15:30:45.117 (0) end_tran ABORT
+21-02-24 15:30:45.118 (1) execute srv_h_id 1 INSERT INTO t VALUES (3)
+21-02-24 15:30:45.119 (1) execute 0 tuple 1 time 0.001
+21-02-24 15:30:45.120 (0) end_tran COMMIT
+";
+        let mut db = Database::new(":memory:")?;
+        db.initialize()?;
+        let mut parser = SqlLogEntries::new(log.as_bytes());
+        let mut rows = TransactionRows::default();
+        db.process_entries("a.sql.log", parser.by_ref(), &mut rows)?;
+        db.insert_transactions("a.sql.log", &parser.take_transactions(), &mut rows)?;
+
+        let outcomes = db
+            .conn
+            .prepare(
+                "SELECT outcome FROM logs JOIN transactions ON transactions.id = logs.transaction_id
+                ORDER BY logs.id",
+            )?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        assert_eq!(outcomes, vec!["ABORT", "ABORT", "COMMIT"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bind_mismatch() -> Result<()> {
+        let log = "\
+[Q1]-------------------------------------------
+broker1_2.sql.log:5
+21-02-24 15:30:45.123 (1) execute srv_h_id 1 SELECT * FROM t WHERE a = ? AND b = ?
+21-02-24 15:30:45.123 (1) bind 1 : INT 1
+21-02-24 15:30:45.124 (1) execute 0 tuple 1 time 0.001
+";
+        let mut db = Database::new(":memory:")?;
+        db.initialize()?;
+        db.process_entries(
+            "log_top.q",
+            LogEntries::new(log.as_bytes()),
+            &mut TransactionRows::default(),
+        )?;
+
+        let row = db.conn.query_row(
+            "SELECT bind_status, mismatch_cause, replaced_query FROM logs",
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            },
+        )?;
+        assert_eq!(
+            row,
+            (
+                "mismatch".to_string(),
+                Some("missing_binds".to_string()),
+                "SELECT * FROM t WHERE a = 1 AND b = ?/* unbound */".to_string()
+            )
         );
-        pb
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_statements() -> Result<()> {
+        let log = "\
+[Q1]-------------------------------------------
+broker1_2.sql.log:5
+21-02-24 15:30:45.123 (1) execute_all srv_h_id 1 INSERT INTO t VALUES (?, ?)
+21-02-24 15:30:45.123 (1) bind 1 : INT 1
+21-02-24 15:30:45.123 (1) bind 2 : VARCHAR (2)a
+21-02-24 15:30:45.123 (1) bind 3 : INT 2
+21-02-24 15:30:45.123 (1) bind 4 : VARCHAR (2)b
+21-02-24 15:30:45.124 (1) execute_all 0 tuple 2 time 0.001
+";
+        let mut db = Database::new(":memory:")?;
+        db.initialize()?;
+        db.process_entries(
+            "log_top.q",
+            LogEntries::new(log.as_bytes()),
+            &mut TransactionRows::default(),
+        )?;
+
+        let (status, kind, batch_size) = db.conn.query_row(
+            "SELECT bind_status, execute_kind, batch_size FROM logs",
+            [],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<i64>>(2)?,
+                ))
+            },
+        )?;
+        assert_eq!(
+            (status.as_str(), kind.as_str(), batch_size),
+            ("batch", "execute_all", Some(2))
+        );
+
+        let statements = db
+            .conn
+            .prepare("SELECT replaced_query FROM batch_statements ORDER BY set_no")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        assert_eq!(
+            statements,
+            vec![
+                "INSERT INTO t VALUES (1, 'a')",
+                "INSERT INTO t VALUES (2, 'b')"
+            ]
+        );
+
+        // A single parameter set is an ordinary execution.
+        let log = "\
+[Q2]-------------------------------------------
+21-02-24 15:30:46.123 (1) execute_all srv_h_id 1 INSERT INTO t VALUES (?, ?)
+21-02-24 15:30:46.123 (1) bind 1 : INT 3
+21-02-24 15:30:46.123 (1) bind 2 : VARCHAR (2)c
+";
+        db.process_entries(
+            "log_top.q",
+            LogEntries::new(log.as_bytes()),
+            &mut TransactionRows::default(),
+        )?;
+        let (status, batch_size) = db.conn.query_row(
+            "SELECT bind_status, batch_size FROM logs WHERE query_no = '2'",
+            [],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<i64>>(1)?)),
+        )?;
+        assert_eq!((status.as_str(), batch_size), ("ok", None));
+        let sets: i64 = db
+            .conn
+            .query_row("SELECT COUNT(*) FROM batch_statements", [], |row| {
+                row.get(0)
+            })?;
+        assert_eq!(sets, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_old_schema() -> Result<()> {
+        let mut db = Database::new(":memory:")?;
+        // The `logs` table written by the first release.
+        db.conn.execute(
+            "CREATE TABLE logs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                query_no TEXT NOT NULL,
+                filename TEXT NOT NULL,
+                original_query TEXT NOT NULL,
+                replaced_query TEXT,
+                bind_vars JSON NOT NULL
+            )",
+            [],
+        )?;
+        db.conn.execute(
+            "INSERT INTO logs (query_no, filename, original_query, replaced_query, bind_vars)
+            VALUES ('1', 'a.sql.log', 'SELECT 1', 'SELECT 1', '[]')",
+            [],
+        )?;
+
+        db.initialize()?;
+        let log = "\
+[Q2]-------------------------------------------
+broker1_2.sql.log:5
+21-02-24 15:30:45.123 (1) execute srv_h_id 1 SELECT 2
+21-02-24 15:30:45.124 (1) execute 0 tuple 1 time 0.001
+";
+        db.process_entries(
+            "log_top.q",
+            LogEntries::new(log.as_bytes()),
+            &mut TransactionRows::default(),
+        )?;
+
+        let rows = db
+            .conn
+            .prepare("SELECT query_no, source_file, bind_status FROM logs ORDER BY id")?
+            .query_map([], |row| {
+                Ok(format!(
+                    "{} {} {}",
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        assert_eq!(rows, vec!["1  ok", "2 log_top.q ok"]);
+
+        let version: i32 = db
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        assert_eq!(version, SCHEMA_VERSION);
+        // A second run finds the schema up to date.
+        db.initialize()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_stats_capture() -> Result<()> {
+        let log = "\
+[Q1]-------------------------------------------
+broker1_2.sql.log:5
+21-02-24 15:30:45.123 (1) execute srv_h_id 1 SELECT 1
+21-02-24 15:30:45.124 (1) execute 0 tuple 1 time 0.001
+";
+        let res = "\
+[Q1]       0.010    0.001    0.005    10 (0)
+";
+        let mut db = Database::new(":memory:")?;
+        db.initialize()?;
+        db.process_entries(
+            "a/log_top.q",
+            LogEntries::new(log.as_bytes()),
+            &mut TransactionRows::default(),
+        )?;
+        db.process_entries(
+            "b/log_top.q",
+            LogEntries::new(log.as_bytes()),
+            &mut TransactionRows::default(),
+        )?;
+        db.insert_query_stats(
+            "b/log_top.res",
+            Some("b/log_top.q"),
+            ResEntries::new(res.as_bytes()),
+        )?;
+
+        let sources = db
+            .conn
+            .prepare(
+                "SELECT logs.source_file FROM logs JOIN query_stats
+                ON query_stats.capture = logs.source_file AND query_stats.query_no = logs.query_no",
+            )?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        assert_eq!(sources, vec!["b/log_top.q"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_transactions_reloaded() -> Result<()> {
+        let log = "\
+21-02-24 15:30:45.112 (1) execute srv_h_id 1 INSERT INTO t VALUES (1)
+21-02-24 15:30:45.113 (1) execute 0 tuple 1 time 0.001
+21-02-24 15:30:45.117 (0) end_tran ABORT
+";
+        let mut db = Database::new(":memory:")?;
+        db.initialize()?;
+        // The same file loaded twice, the second time in two batches.
+        for batch_size in [usize::MAX, 0] {
+            let mut parser = SqlLogEntries::new(log.as_bytes());
+            let mut rows = TransactionRows::default();
+            db.process_entries("a.sql.log", parser.by_ref().take(batch_size), &mut rows)?;
+            db.insert_transactions("a.sql.log", &parser.take_transactions(), &mut rows)?;
+            db.process_entries("a.sql.log", parser.by_ref(), &mut rows)?;
+            db.insert_transactions("a.sql.log", &parser.take_transactions(), &mut rows)?;
+        }
+
+        let links = db
+            .conn
+            .prepare(
+                "SELECT logs.id, transactions.id, outcome, execution_count FROM logs
+                JOIN transactions ON transactions.id = logs.transaction_id ORDER BY logs.id",
+            )?
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, i64>(3)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        assert_eq!(
+            links,
+            vec![
+                (1, 1, "ABORT".to_string(), 1),
+                (2, 2, "ABORT".to_string(), 1)
+            ]
+        );
+        let count: i64 = db
+            .conn
+            .query_row("SELECT COUNT(*) FROM transactions", [], |row| row.get(0))?;
+        assert_eq!(count, 2);
+        Ok(())
     }
 }
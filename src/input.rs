@@ -0,0 +1,218 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use flate2::bufread::MultiGzDecoder;
+use xz2::bufread::XzDecoder;
+
+/// Number of bytes needed to recognize every compression format.
+const MAGIC_LEN: u64 = 6;
+
+/// Compression formats recognized in log inputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    /// Detects the compression format from the first bytes of the input.
+    pub fn detect(header: &[u8]) -> Self {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else if header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// Wraps `reader` in a decoder matching its magic bytes, so that compressed
+/// logs can be parsed without decompressing them to disk first.
+pub fn decompress<'a, R: BufRead + 'a>(mut reader: R) -> Result<Box<dyn BufRead + 'a>> {
+    // A pipe may hand out fewer bytes at a time than the magic bytes span,
+    // so read until they are all in or the input ends.
+    let mut header = Vec::new();
+    reader.by_ref().take(MAGIC_LEN).read_to_end(&mut header)?;
+    let compression = Compression::detect(&header);
+    let reader = Cursor::new(header).chain(reader);
+
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+        Compression::Xz => Box::new(BufReader::new(XzDecoder::new_multi_decoder(reader))),
+    })
+}
+
+/// A log input named on the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputSource {
+    Stdin,
+    File(PathBuf),
+}
+
+impl InputSource {
+    /// Name recorded in the database for entries read from this input.
+    pub fn name(&self) -> String {
+        match self {
+            InputSource::Stdin => "<stdin>".to_string(),
+            InputSource::File(path) => path.display().to_string(),
+        }
+    }
+
+    /// File name of the input, from which the broker name and CAS number
+    /// are taken for SQL and access logs. Empty for stdin.
+    pub fn file_name(&self) -> String {
+        match self {
+            InputSource::Stdin => String::new(),
+            InputSource::File(path) => path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Size of the input in bytes, if known in advance.
+    pub fn size(&self) -> Option<u64> {
+        match self {
+            InputSource::Stdin => None,
+            InputSource::File(path) => fs::metadata(path).ok().map(|m| m.len()),
+        }
+    }
+
+    pub fn open(&self) -> Result<Box<dyn Read>> {
+        Ok(match self {
+            InputSource::Stdin => Box::new(io::stdin()),
+            InputSource::File(path) => Box::new(
+                File::open(path)
+                    .with_context(|| format!("Failed to open log file: {}", path.display()))?,
+            ),
+        })
+    }
+}
+
+/// Resolves command line arguments into inputs.
+///
+/// `-` stands for stdin and a directory expands to the regular files it
+/// contains, in file name order.
+pub fn collect_inputs(args: &[String]) -> Result<Vec<InputSource>> {
+    let mut inputs = Vec::new();
+
+    for arg in args {
+        if arg == "-" {
+            inputs.push(InputSource::Stdin);
+            continue;
+        }
+
+        let path = PathBuf::from(arg);
+        if path.is_dir() {
+            let mut files = fs::read_dir(&path)
+                .with_context(|| format!("Failed to read directory: {}", path.display()))?
+                .map(|entry| entry.map(|e| e.path()))
+                .collect::<io::Result<Vec<_>>>()?;
+            files.retain(|file| file.is_file());
+            files.sort();
+            inputs.extend(files.into_iter().map(InputSource::File));
+        } else {
+            inputs.push(InputSource::File(path));
+        }
+    }
+
+    Ok(inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    const LOG: &str = "[Q1]-----\n21-02-24 15:30:45.123 (1) execute srv_h_id 1 SELECT 1\n";
+
+    fn read_all(input: &[u8]) -> Result<String> {
+        let mut content = String::new();
+        decompress(input)?.read_to_string(&mut content)?;
+        Ok(content)
+    }
+
+    #[test]
+    fn test_plain_input() -> Result<()> {
+        assert_eq!(Compression::detect(LOG.as_bytes()), Compression::None);
+        assert_eq!(read_all(LOG.as_bytes())?, LOG);
+        Ok(())
+    }
+
+    #[test]
+    fn test_gzip_input() -> Result<()> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(LOG.as_bytes())?;
+        let compressed = encoder.finish()?;
+
+        assert_eq!(Compression::detect(&compressed), Compression::Gzip);
+        assert_eq!(read_all(&compressed)?, LOG);
+        Ok(())
+    }
+
+    #[test]
+    fn test_zstd_input() -> Result<()> {
+        let compressed = zstd::encode_all(LOG.as_bytes(), 0)?;
+
+        assert_eq!(Compression::detect(&compressed), Compression::Zstd);
+        assert_eq!(read_all(&compressed)?, LOG);
+        Ok(())
+    }
+
+    #[test]
+    fn test_xz_input() -> Result<()> {
+        let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+        encoder.write_all(LOG.as_bytes())?;
+        let compressed = encoder.finish()?;
+
+        assert_eq!(Compression::detect(&compressed), Compression::Xz);
+        assert_eq!(read_all(&compressed)?, LOG);
+        Ok(())
+    }
+
+    #[test]
+    fn test_magic_split_across_reads() -> Result<()> {
+        let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+        encoder.write_all(LOG.as_bytes())?;
+        let compressed = encoder.finish()?;
+
+        // As read from a pipe handing out one byte at a time.
+        let mut content = String::new();
+        decompress(BufReader::with_capacity(1, compressed.as_slice()))?
+            .read_to_string(&mut content)?;
+        assert_eq!(content, LOG);
+        assert_eq!(read_all(b"[Q")?, "[Q");
+        Ok(())
+    }
+
+    #[test]
+    fn test_collect_inputs() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("logtopbind-inputs-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested"))?;
+        fs::write(dir.join("b.q"), LOG)?;
+        fs::write(dir.join("a.q"), LOG)?;
+
+        let args = vec!["-".to_string(), dir.display().to_string()];
+        let inputs = collect_inputs(&args)?;
+        fs::remove_dir_all(&dir)?;
+
+        assert_eq!(
+            inputs,
+            vec![
+                InputSource::Stdin,
+                InputSource::File(dir.join("a.q")),
+                InputSource::File(dir.join("b.q")),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
 pub mod db;
+pub mod input;
 pub mod parser;
 pub mod utils;
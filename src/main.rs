@@ -1,80 +1,413 @@
 use anyhow::Result;
+use clap::{
+    builder::{PossibleValue, PossibleValuesParser, TypedValueParser},
+    Parser,
+};
 use cubrid_logtopbind_rs::{
-    db::Database,
-    parser::{parse_log_entries, LogEntry},
-    utils::print_help,
+    db::{Database, TransactionRows, BATCH_SIZE},
+    input::{collect_inputs, InputSource},
+    parser::{
+        format_by_name, AccessLogEntries, CubridVersion, Decoding, Diagnostic, DiagnosticKind,
+        EntryStream, InputEncoding, LogEntry, LogFormat, LogParser, MismatchCause, ResEntries,
+        LOG_FORMATS,
+    },
 };
+use indicatif::ProgressBar;
+use serde_json::json;
 use std::{
-    env,
-    fs::{self, File},
-    io::BufWriter,
-    io::Write,
+    fs::File,
+    io::{BufRead, BufWriter, Write},
 };
 
-fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
-
-    if args.len() < 2 || args.contains(&"-h".to_string()) || args.contains(&"--help".to_string()) {
-        print_help();
-        return Ok(());
-    }
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Log files or directories to ingest (`-` reads from stdin)
+    #[arg(required = true)]
+    log_files: Vec<String>,
+    /// Format of the input logs (`auto` detects log-top and sql-log inputs
+    /// from their first lines)
+    #[arg(long, value_parser = input_format_parser(), default_value = "auto")]
+    format: InputFormat,
+    /// Number of threads used to parse the log (1 disables parallel parsing)
+    #[arg(short = 'j', long, default_value_t = 1)]
+    threads: usize,
+    /// CUBRID release that wrote the logs, e.g. `9.3` or `11.2` (`auto`
+    /// accepts the line formats of every supported release)
+    #[arg(long, default_value_t = CubridVersion::Auto)]
+    cubrid_version: CubridVersion,
+    /// Year of the CUBRID 9.x logs, whose timestamps are written without one
+    /// and are otherwise stored without a time
+    #[arg(long, value_name = "YEAR")]
+    log_year: Option<i32>,
+    /// Character encoding of the logs, e.g. `euc-kr` (`auto` detects it from
+    /// lines that are not valid UTF-8)
+    #[arg(long, default_value_t = InputEncoding::Auto)]
+    encoding: InputEncoding,
+    /// Replace bytes that cannot be decoded instead of failing
+    #[arg(long)]
+    lossy: bool,
+    /// Abort on the first unparseable bind value instead of flagging the entry
+    #[arg(long)]
+    strict: bool,
+    /// Also write parse diagnostics to this JSON file
+    #[arg(long, value_name = "PATH")]
+    diagnostics_json: Option<String>,
+}
 
-    let log_file = &args[1];
-    let content = fs::read_to_string(log_file)?;
+/// Format of the inputs, given by `--format`.
+#[derive(Clone, Copy)]
+enum InputFormat {
+    /// Detect the format of each input
+    Auto,
+    /// One of the [`LOG_FORMATS`], whose records are log entries
+    Log(&'static dyn LogFormat),
+    /// Broker access log (`<broker>.access`), loaded into the access_log table
+    AccessLog,
+    /// Per-query statistics of `broker_log_top` (`log_top.res`), loaded into
+    /// the query_stats table
+    Res,
+}
 
-    println!("Parsing log entries...");
-    let entries = parse_log_entries(&content)?;
+/// Accepts `auto`, the name of any format in [`LOG_FORMATS`], `access-log`
+/// and `res`.
+fn input_format_parser() -> impl TypedValueParser<Value = InputFormat> {
+    let log_formats = LOG_FORMATS
+        .iter()
+        .map(|format| PossibleValue::new(format.name()).help(format.description()));
+    let values = [PossibleValue::new("auto").help("Detect the format of each input")]
+        .into_iter()
+        .chain(log_formats)
+        .chain([
+            PossibleValue::new("access-log")
+                .help("Broker access log (`<broker>.access`), loaded into the access_log table"),
+            PossibleValue::new("res").help(
+                "Per-query statistics of `broker_log_top` (`log_top.res`), loaded into the \
+                 query_stats table",
+            ),
+        ]);
+    PossibleValuesParser::new(values).map(|name| match name.as_str() {
+        "auto" => InputFormat::Auto,
+        "access-log" => InputFormat::AccessLog,
+        "res" => InputFormat::Res,
+        // Only the names listed above are accepted.
+        name => InputFormat::Log(format_by_name(name).expect("known log format")),
+    })
+}
 
-    let entries = process_entries(entries)?;
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let inputs = collect_inputs(&cli.log_files)?;
 
     let mut db = Database::new("queries.db")?;
     db.initialize()?;
 
-    println!("Processing log entries...");
-    db.process_entries(&entries)?;
+    // Open the log file in write mode (truncates it if it already exists).
+    let mut mismatch_log = BufWriter::new(File::create("deleted_entries.log")?);
 
-    Ok(())
-}
+    let lenient = !cli.strict;
+    let decoding = Decoding {
+        encoding: cli.encoding,
+        lossy: cli.lossy,
+    };
+    // Built once, so that its patterns are compiled only once for all inputs.
+    let mut builder = LogParser::builder()
+        .lenient(lenient)
+        .encoding(cli.encoding)
+        .lossy(cli.lossy)
+        .version(cli.cubrid_version)
+        .threads(cli.threads)
+        .progress(true);
+    if let Some(year) = cli.log_year {
+        builder = builder.log_year(year);
+    }
+    let log_parser = builder.build()?;
+    let mut flagged = 0;
+    let mut diagnostics = DiagnosticRecorder {
+        count: 0,
+        lossy_lines: 0,
+        report: cli
+            .diagnostics_json
+            .as_deref()
+            .map(JsonReport::create)
+            .transpose()?,
+    };
+
+    for input in &inputs {
+        let source_file = input.name();
+        println!("Processing log entries from {}...", source_file);
 
-fn process_entries(entries: Vec<LogEntry>) -> std::io::Result<Vec<LogEntry>> {
-    // Partition the entries into valid and invalid groups.
-    let (filtered_entries, deleted_entries): (Vec<LogEntry>, Vec<LogEntry>) =
-        entries.into_iter().partition(|entry| {
-            if entry.bind_statements.is_empty() {
-                return true;
+        let (inserted, progress_bar) = match cli.format {
+            InputFormat::AccessLog => {
+                let progress_bar = log_parser.progress_bar(input.size());
+                let reader = log_parser.open(input, &progress_bar)?;
+                let mut parser = AccessLogEntries::new(reader)
+                    .filename(&input.file_name())
+                    .decoding(decoding);
+                let inserted = insert_batches(
+                    &mut db,
+                    &mut parser,
+                    &source_file,
+                    &mut diagnostics,
+                    |db, parser| db.insert_access_log(&source_file, parser.take(BATCH_SIZE)),
+                )?;
+                (inserted, progress_bar)
             }
-            let placeholder_count = entry.query.bytes().filter(|&b| b == b'?').count();
-            placeholder_count == entry.bind_statements.len()
-        });
-
-    // Print a debug log to the console for the problematic entries.
-    println!("Deleted entries due to bind variable numbers mismatch:");
-    for entry in &deleted_entries {
-        println!("Entry number: {}", entry.query_no);
-        println!("bind statements: {}", entry.bind_statements.len(),);
+            InputFormat::Res => {
+                let progress_bar = log_parser.progress_bar(input.size());
+                let reader = log_parser.open(input, &progress_bar)?;
+                let mut parser = ResEntries::new(reader).decoding(decoding);
+                let inserted = insert_batches(
+                    &mut db,
+                    &mut parser,
+                    &source_file,
+                    &mut diagnostics,
+                    |db, parser| {
+                        let capture = res_capture(input);
+                        db.insert_query_stats(
+                            &source_file,
+                            capture.as_deref(),
+                            parser.take(BATCH_SIZE),
+                        )
+                    },
+                )?;
+                (inserted, progress_bar)
+            }
+            InputFormat::Auto | InputFormat::Log(_) => {
+                let format = match cli.format {
+                    InputFormat::Log(format) => Some(format),
+                    _ => None,
+                };
+                let (mut parser, log_format, progress_bar) =
+                    log_parser.parse_input(input, format)?;
+                if format.is_none() {
+                    progress_bar.suspend(|| {
+                        println!("Detected {} format", log_format.name());
+                    });
+                }
+                let mut transactions = TransactionRows::default();
+                let inserted = insert_batches(
+                    &mut db,
+                    &mut parser,
+                    &source_file,
+                    &mut diagnostics,
+                    |db, parser| {
+                        let entries = parser.take(BATCH_SIZE).inspect(|entry| {
+                            if matches!(entry, Ok(entry) if entry.parse_error.is_some()) {
+                                flagged += 1;
+                            }
+                        });
+                        let entries = report_mismatches(entries, &progress_bar, &mut mismatch_log);
+                        let inserted =
+                            db.process_entries(&source_file, entries, &mut transactions)?;
+                        // Written as they close, so that they are not all
+                        // held until the end of the input.
+                        db.insert_transactions(
+                            &source_file,
+                            &parser.take_transactions(),
+                            &mut transactions,
+                        )?;
+                        Ok(inserted)
+                    },
+                )?;
+                (inserted, progress_bar)
+            }
+        };
+
+        progress_bar.finish_and_clear();
+        println!("{} log entries processed successfully!", inserted);
+    }
+    mismatch_log.flush()?;
+
+    if flagged > 0 {
         println!(
-            "placeholder_count: {}",
-            entry.query.bytes().filter(|&b| b == b'?').count()
+            "{} entries contain bind values that could not be parsed (see the parse_error column)",
+            flagged
         );
-        let truncated = if entry.query.len() > 30 {
-            format!("{:.30} ...(skipped)", entry.query)
-        } else {
-            entry.query.to_string()
-        };
-        println!("Original query: {}", truncated);
-        println!("-------------------------------------");
     }
+    if diagnostics.lossy_lines > 0 {
+        println!(
+            "{} lines contained bytes that could not be decoded and were replaced",
+            diagnostics.lossy_lines
+        );
+    }
+    if diagnostics.count > 0 {
+        println!(
+            "{} parse diagnostics recorded in the diagnostics table",
+            diagnostics.count
+        );
+    }
+    if let (Some(report), Some(path)) = (diagnostics.report, &cli.diagnostics_json) {
+        report.finish()?;
+        println!("Diagnostics report written to {}", path);
+    }
+
+    Ok(())
+}
+
+/// Name of the `log_top.q` input a `log_top.res` input belongs to:
+/// `broker_log_top` writes both next to each other, with the same stem.
+fn res_capture(input: &InputSource) -> Option<String> {
+    match input {
+        InputSource::Stdin => None,
+        InputSource::File(path) => Some(path.with_extension("q").display().to_string()),
+    }
+}
+
+/// A parser whose diagnostics can be drained as it goes.
+trait TakeDiagnostics {
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic>;
+}
+
+impl TakeDiagnostics for Box<dyn EntryStream + '_> {
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        EntryStream::take_diagnostics(self.as_mut())
+    }
+}
+
+impl<R: BufRead> TakeDiagnostics for AccessLogEntries<R> {
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        AccessLogEntries::take_diagnostics(self)
+    }
+}
 
-    // Open the log file in append mode (creates it if it doesn't exist).
-    // This ensures that previous log entries are preserved.
-    let file = File::create("deleted_entries.log")?;
-    let mut writer = BufWriter::new(file);
+impl<R: BufRead> TakeDiagnostics for ResEntries<R> {
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        ResEntries::take_diagnostics(self)
+    }
+}
 
-    // Write a detailed debug log to the file.
-    for entry in deleted_entries {
-        writeln!(writer, "{}", entry.query_no)?;
+/// Inserts what `parser` yields, [`BATCH_SIZE`] rows at a time with
+/// `insert_batch`, and records the diagnostics reported so far after every
+/// batch, so that they are never all held in memory. Returns the number of
+/// inserted rows.
+fn insert_batches<P: TakeDiagnostics>(
+    db: &mut Database,
+    parser: &mut P,
+    source_file: &str,
+    diagnostics: &mut DiagnosticRecorder,
+    mut insert_batch: impl FnMut(&mut Database, &mut P) -> Result<usize>,
+) -> Result<usize> {
+    let mut inserted = 0;
+    loop {
+        let batch = insert_batch(db, parser)?;
+        diagnostics.record(db, source_file, parser.take_diagnostics())?;
+        inserted += batch;
+        if batch < BATCH_SIZE {
+            return Ok(inserted);
+        }
     }
-    writer.flush()?;
+}
+
+/// Records diagnostics as they are reported: in the diagnostics table, in
+/// the totals printed at the end and, with `--diagnostics-json`, in the
+/// report.
+struct DiagnosticRecorder {
+    count: usize,
+    lossy_lines: usize,
+    report: Option<JsonReport>,
+}
+
+impl DiagnosticRecorder {
+    fn record(
+        &mut self,
+        db: &mut Database,
+        source_file: &str,
+        diagnostics: Vec<Diagnostic>,
+    ) -> Result<()> {
+        if diagnostics.is_empty() {
+            return Ok(());
+        }
+        db.insert_diagnostics(source_file, &diagnostics)?;
+        self.count += diagnostics.len();
+        self.lossy_lines += diagnostics
+            .iter()
+            .filter(|d| d.kind == DiagnosticKind::InvalidEncoding)
+            .count();
+        if let Some(report) = &mut self.report {
+            for diagnostic in &diagnostics {
+                report.push(&diagnostic_to_json(source_file, diagnostic))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A JSON array written one element at a time.
+struct JsonReport {
+    writer: BufWriter<File>,
+    empty: bool,
+}
+
+impl JsonReport {
+    fn create(path: &str) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write!(writer, "[")?;
+        Ok(Self {
+            writer,
+            empty: true,
+        })
+    }
+
+    fn push(&mut self, value: &serde_json::Value) -> Result<()> {
+        write!(self.writer, "{}\n  ", if self.empty { "" } else { "," })?;
+        serde_json::to_writer(&mut self.writer, value)?;
+        self.empty = false;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<()> {
+        writeln!(self.writer, "{}]", if self.empty { "" } else { "\n" })?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+fn diagnostic_to_json(source_file: &str, diagnostic: &Diagnostic) -> serde_json::Value {
+    json!({
+        "source_file": source_file,
+        "kind": diagnostic.kind.name(),
+        "line_no": diagnostic.line_no,
+        "byte_offset": diagnostic.byte_offset,
+        "query_no": diagnostic.query_no,
+        "text": diagnostic.text,
+        "message": diagnostic.message,
+    })
+}
+
+/// Reports the entries whose bind variable count does not match the number
+/// of placeholders in the query, recording their query numbers in `writer`.
+/// The entries are kept and passed on, to be stored with their cause.
+fn report_mismatches<'a, I>(
+    entries: I,
+    progress_bar: &'a ProgressBar,
+    writer: &'a mut impl Write,
+) -> impl Iterator<Item = Result<LogEntry>> + 'a
+where
+    I: Iterator<Item = Result<LogEntry>> + 'a,
+{
+    entries.map(move |entry| {
+        let entry = entry?;
+        if let Some(cause) = entry.bind_mismatch() {
+            // Print a debug log to the console for the problematic entry.
+            progress_bar.suspend(|| print_mismatched_entry(&entry, cause));
+            writeln!(writer, "{}", entry.query_no)?;
+        }
+        Ok(entry)
+    })
+}
 
-    Ok(filtered_entries)
+fn print_mismatched_entry(entry: &LogEntry, cause: MismatchCause) {
+    println!("Entry kept with partially bound query due to bind variable numbers mismatch:");
+    println!("Entry number: {}", entry.query_no);
+    println!("bind statements: {}", entry.bind_statements.len(),);
+    println!("placeholder_count: {}", entry.placeholder_count());
+    println!("cause: {}", cause);
+    let truncated = if entry.query.len() > 30 {
+        format!("{:.30} ...(skipped)", entry.query)
+    } else {
+        entry.query.to_string()
+    };
+    println!("Original query: {}", truncated);
+    println!("-------------------------------------");
 }
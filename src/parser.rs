@@ -1,111 +1,373 @@
+mod bind_value;
+mod diagnostic;
+mod encoding;
+mod format;
 mod log_entry;
+mod placeholder;
 
-pub use log_entry::LogEntry;
+pub use bind_value::{BindType, BindValue};
+pub use diagnostic::{Diagnostic, DiagnosticKind, EntryStream};
+pub use encoding::{Decoding, InputEncoding};
+pub use format::{
+    detect_format, format_by_name, sniff, LogFormat, LogTopFormat, SqlLogFormat, LOG_FORMATS,
+    SNIFF_LEN,
+};
+pub use log_entry::{ExecuteKind, LogEntry, MismatchCause, UNBOUND_MARKER};
+pub use placeholder::{
+    count_parameters, count_placeholders, find_placeholders, Placeholder, PlaceholderStyle,
+};
 
-use indicatif::{ProgressBar, ProgressStyle};
+use std::io::BufRead;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use encoding_rs::Encoding;
+use line_reader::{BufferedLine, LineReader};
+use multiline::{continues_unsized, PendingValue};
 use parse_line::parse_bind_value;
-use parse_line::parse_line;
+use parse_line::split_cas_log_name;
+use parse_line::LinePatterns;
 use parse_line::ParsedLine;
-use regex::Regex;
 
+mod access_log;
+mod line_reader;
+mod log_parser;
+mod multiline;
+mod parallel;
 mod parse_line;
+mod res_file;
+mod sql_log;
+mod transaction;
+mod version;
 
+pub use access_log::{AccessLogEntries, AccessLogEntry};
+pub use log_parser::{LogParser, LogParserBuilder};
+pub use parallel::ParallelLogEntries;
+pub use res_file::{QueryStats, ResEntries};
+pub use sql_log::SqlLogEntries;
+pub use transaction::{Transaction, TransactionOutcome};
+pub use version::CubridVersion;
+
+/// Matches the `[Qn]-----` header that starts every block of a `log_top.q` file.
+const QUERY_NO_PATTERN: &str = r"^\[Q(\d+)\]-+$";
+
+/// Parses a whole log held in memory.
+///
+/// This is a convenience wrapper around [`LogEntries`]; prefer the iterator
+/// for large files so that memory usage does not grow with the input size.
 pub fn parse_log_entries(content: &str) -> Result<Vec<LogEntry>> {
-    let timestamp_pattern =
-        r"(?:\d{2})-(?:\d{2})-(?:\d{2})\s(?:\d{2}):(?:\d{2}):(?:\d{2})\.(?:\d{3})\s\((?:\d+)\)";
-
-    let re_query_no = Regex::new(r"^\[Q(\d+)\]-+$").unwrap();
-    let re_query = Regex::new(&format!(
-        r"^{} (?:execute_all|execute) srv_h_id \d* (.*)$",
-        timestamp_pattern
-    ))
-    .unwrap();
-    let re_bind = Regex::new(&format!(r"^{} bind \d+ : ", timestamp_pattern)).unwrap();
-
-    let re_end = Regex::new(&format!(
-        r"^{} (?:execute_all|execute) (error:-)?\d+ tuple \d+ time .*$",
-        timestamp_pattern
-    ))
-    .unwrap();
-    let re_filename =
-        Regex::new(r"^([a-zA-Z0-9][a-zA-Z0-9_\.-]{0,150}[a-zA-Z0-9]):\d{1,6}$").unwrap();
-
-    let lines: Vec<&str> = content.lines().collect();
-    let pb = ProgressBar::new(lines.len() as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("  [{bar:40.cyan/blue}] {pos}/{len} ({eta})")?
-            .progress_chars("#>-"),
-    );
-
-    let mut entries = Vec::new();
-    let mut current = LogEntry::default();
-
-    let mut after_bind = false;
-
-    for line in lines {
-        pb.inc(1);
+    LogEntries::new(content.as_bytes()).collect()
+}
+
+/// Like [`parse_log_entries`], but also returns the diagnostics collected
+/// while parsing.
+pub fn parse_log_entries_with_diagnostics(
+    content: &str,
+) -> Result<(Vec<LogEntry>, Vec<Diagnostic>)> {
+    let mut parser = LogEntries::new(content.as_bytes());
+    let entries = parser.by_ref().collect::<Result<Vec<_>>>()?;
+    Ok((entries, parser.take_diagnostics()))
+}
+
+/// Streaming parser over a `log_top.q` input.
+///
+/// Lines are read one at a time from the underlying reader and a [`LogEntry`]
+/// is yielded as soon as its block is complete, so only the block currently
+/// being parsed is kept in memory.
+///
+/// By default an unparseable bind value ends the iteration with an error. In
+/// lenient mode the bind is kept as a [`BindType::Unparsed`] value holding the
+/// raw text and the entry is flagged through [`LogEntry::parse_error`].
+/// Problems found along the way are collected as [`Diagnostic`]s.
+///
+/// A string bind value that spans several lines is read up to its declared
+/// byte length, even if some of its lines look like log lines. A value that
+/// the input cuts short is flagged through [`BindValue::truncated`]. Strings
+/// written without a length take the following lines up to the next log
+/// line instead.
+pub struct LogEntries<R> {
+    lines: LineReader<R>,
+    patterns: LinePatterns,
+    line: String,
+    current: LogEntry,
+    /// Set after a string bind without a declared length, whose value may
+    /// continue on the following lines.
+    after_bind: bool,
+    /// Rest of the last bind value, when it runs past its line.
+    pending: Option<PendingValue>,
+    lenient: bool,
+    diagnostics: Vec<Diagnostic>,
+    finished: bool,
+}
+
+impl<R: BufRead> LogEntries<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: LineReader::new(reader),
+            patterns: LinePatterns::default(),
+            line: String::new(),
+            current: LogEntry::default(),
+            after_bind: false,
+            pending: None,
+            lenient: false,
+            diagnostics: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// Sets the position of the reader within the whole input, so that
+    /// diagnostics for a part of a file point at the right place.
+    pub(crate) fn starting_at(mut self, lines_before: u64, byte_offset: u64) -> Self {
+        self.lines = self.lines.starting_at(lines_before, byte_offset);
+        self
+    }
+
+    /// Keeps entries with unparseable bind values instead of failing.
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Sets how the input bytes are decoded.
+    pub fn decoding(mut self, decoding: Decoding) -> Self {
+        self.lines = self.lines.decoding(decoding);
+        self
+    }
+
+    /// Sets the encoding detected for the whole input, used for the lines
+    /// that are not valid UTF-8 when the encoding is detected.
+    pub(crate) fn detected_encoding(mut self, encoding: Option<&'static Encoding>) -> Self {
+        self.lines = self.lines.detected_encoding(encoding);
+        self
+    }
+
+    /// Restricts the accepted line formats to those of `version`.
+    pub fn version(mut self, version: CubridVersion) -> Self {
+        self.patterns = LinePatterns::new(version);
+        self
+    }
+
+    pub(crate) fn patterns(mut self, patterns: LinePatterns) -> Self {
+        self.patterns = patterns;
+        self
+    }
+
+    /// Feeds one line into the current block. Returns the previous entry when
+    /// the line starts a new block.
+    fn process_line(&mut self) -> Result<Option<LogEntry>> {
+        let line = std::mem::take(&mut self.line);
+        let result = if self.pending.is_some() {
+            self.continue_value(&line);
+            Ok(None)
+        } else {
+            self.process_parsed_line(&line)
+        };
+        self.line = line;
+        result
+    }
+
+    /// Feeds a line to the value of the last bind, which runs past its line.
+    fn continue_value(&mut self, line: &str) {
+        let Some(pending) = &mut self.pending else {
+            return;
+        };
+        match pending.push(self.lines.last_line(line)) {
+            Ok(()) if pending.is_complete() => {
+                if let Some(pending) = self.pending.take() {
+                    pending.finish(&mut self.current);
+                }
+            }
+            Ok(()) => {}
+            Err(next) => self.truncate_value(Some(next)),
+        }
+    }
+
+    /// Ends the pending bind value, cut short before `next` or by the end of
+    /// the input, and hands back the lines that do not belong to it.
+    fn truncate_value(&mut self, next: Option<BufferedLine>) {
+        if let Some(pending) = self.pending.take() {
+            let patterns = &self.patterns;
+            let (diagnostic, unread) = pending.truncate(&mut self.current, next, |line| {
+                patterns.parse(line).is_some()
+            });
+            self.diagnostics.push(diagnostic);
+            self.lines.unread(unread);
+        }
+    }
+
+    fn process_parsed_line(&mut self, line: &str) -> Result<Option<LogEntry>> {
+        let mut completed = None;
+
         // First try to parse the line using regexes.
-        match parse_line(
-            line,
-            &re_query_no,
-            &re_bind,
-            &re_query,
-            &re_end,
-            &re_filename,
-        ) {
+        match self.patterns.parse(line) {
             Some(ParsedLine::QueryNo(text)) => {
-                if !current.query_no.is_empty() {
-                    entries.push(current.clone());
-                    current = LogEntry::default();
+                if !self.current.query_no.is_empty() {
+                    completed = Some(std::mem::take(&mut self.current));
                 }
-                current.query_no = text.to_string();
+                self.current.query_no = text.to_string();
                 // Reset the bind flag when starting a new query block.
-                after_bind = false;
+                self.after_bind = false;
             }
             Some(ParsedLine::Bind(text)) => {
-                let text = parse_bind_value(text)?;
-                current.bind_statements.push(text);
-                after_bind = true;
+                push_bind(
+                    &mut self.current,
+                    text,
+                    self.lenient,
+                    &self.lines,
+                    &mut self.diagnostics,
+                )?;
+                if let Some(bind) = self.current.bind_statements.last_mut() {
+                    self.pending = PendingValue::start(bind, text, line, &self.lines);
+                    self.after_bind = continues_unsized(bind);
+                }
             }
-            Some(ParsedLine::Query(text)) => {
-                current.query = text.to_string();
-                after_bind = false;
+            Some(ParsedLine::Query {
+                header,
+                kind,
+                srv_h_id,
+                query,
+            }) => {
+                self.current.query = query.to_string();
+                self.current.execute_kind = kind;
+                self.current.srv_h_id = srv_h_id;
+                self.current.start_time = header.time;
+                self.current.pid = Some(header.pid);
+                self.after_bind = false;
             }
-            Some(ParsedLine::End) => {
-                // You can use this branch to update state or finalize a block if needed.
-                after_bind = false;
+            Some(ParsedLine::End(header, result)) => {
+                self.current.end_time = header.time;
+                self.current.error_code = result.error_code;
+                self.current.tuple_count = Some(result.tuple_count);
+                self.current.elapsed = Some(result.elapsed);
+                self.after_bind = false;
             }
-            Some(ParsedLine::Filename(text)) => {
-                current.filename = text.to_string();
-                after_bind = false;
+            Some(ParsedLine::Filename(text, line_no)) => {
+                self.current.filename = text.to_string();
+                self.current.source_line = Some(line_no);
+                if let Some((broker_name, cas_id)) = split_cas_log_name(text) {
+                    self.current.broker_name = Some(broker_name.to_string());
+                    self.current.cas_id = Some(cas_id);
+                }
+                self.after_bind = false;
             }
-            None if after_bind => {
-                // If no regex matched and we're in an "after_bind" state,
+            None if self.after_bind => {
+                // If no regex matched after a string bind without a length,
                 // treat this as a continuation of the last bind statement.
-                if let Some(last) = current.bind_statements.last_mut() {
+                if let Some(last) = self.current.bind_statements.last_mut() {
                     // Append the line to the previous bind statement.
-                    last.reserve(line.len() + 1);
-                    last.push('\n');
-                    last.push_str(line);
+                    last.value.reserve(line.len() + 1);
+                    last.value.push('\n');
+                    last.value.push_str(line);
                 }
             }
             None if line.is_empty() => {
                 // Ignore empty lines.
             }
             None => {
-                // Log or handle unrecognized lines.
-                println!("Unrecognized line: {}", line);
+                self.diagnostics.push(new_diagnostic(
+                    &self.lines,
+                    &self.current,
+                    DiagnosticKind::UnrecognizedLine,
+                    line,
+                    "Unrecognized line".to_string(),
+                ));
             }
         }
+
+        Ok(completed)
     }
+}
 
-    if !current.query_no.is_empty() {
-        entries.push(current);
+/// Builds a diagnostic for the line last read from `lines`.
+fn new_diagnostic<R>(
+    lines: &LineReader<R>,
+    entry: &LogEntry,
+    kind: DiagnosticKind,
+    text: &str,
+    message: String,
+) -> Diagnostic {
+    Diagnostic {
+        kind,
+        line_no: lines.line_no(),
+        byte_offset: lines.line_offset(),
+        text: text.to_string(),
+        query_no: (!entry.query_no.is_empty()).then(|| entry.query_no.clone()),
+        message,
     }
+}
 
-    pb.finish_with_message("Parsing complete");
-    Ok(entries)
+/// Parses the value of a bind line and appends it to `entry`.
+///
+/// In lenient mode a value that cannot be parsed is kept as a
+/// [`BindType::Unparsed`] bind, the entry is flagged and a diagnostic is
+/// recorded; otherwise the error is returned.
+fn push_bind<R>(
+    entry: &mut LogEntry,
+    text: &str,
+    lenient: bool,
+    lines: &LineReader<R>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<()> {
+    let bind = match parse_bind_value(text) {
+        Ok(bind) => bind,
+        Err(e) => {
+            let diagnostic = new_diagnostic(
+                lines,
+                entry,
+                DiagnosticKind::InvalidBind,
+                text,
+                e.to_string(),
+            );
+            if !lenient {
+                return Err(anyhow!("{}: {}", diagnostic, text.trim()));
+            }
+            entry
+                .parse_error
+                .get_or_insert_with(|| format!("{}: {}", e, text.trim()));
+            diagnostics.push(diagnostic);
+            BindValue::new(BindType::Unparsed, None, text)
+        }
+    };
+    entry.bind_statements.push(bind);
+    Ok(())
+}
+
+impl<R: BufRead> EntryStream for LogEntries<R> {
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+}
+
+impl<R: BufRead> Iterator for LogEntries<R> {
+    type Item = Result<LogEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        loop {
+            match self.lines.read_line(&mut self.line, &mut self.diagnostics) {
+                Ok(true) => match self.process_line() {
+                    Ok(Some(entry)) => return Some(Ok(entry)),
+                    Ok(None) => {}
+                    Err(e) => {
+                        self.finished = true;
+                        return Some(Err(e));
+                    }
+                },
+                Ok(false) if self.pending.is_some() => self.truncate_value(None),
+                Ok(false) => {
+                    self.finished = true;
+                    if self.current.query_no.is_empty() {
+                        return None;
+                    }
+                    return Some(Ok(std::mem::take(&mut self.current)));
+                }
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
 }
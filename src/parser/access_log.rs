@@ -0,0 +1,215 @@
+use std::io::BufRead;
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::line_reader::LineReader;
+use super::{Decoding, Diagnostic, DiagnosticKind};
+
+lazy_static! {
+    /// `<cas> <client ip> <client app> <start> ~ <end> <pid> <error message> <error code>`
+    static ref ACCESS_LINE: Regex = Regex::new(
+        r"^(\d+) (\S+) (\S+) (\d{2}/\d{2}/\d{2} \d{2}:\d{2}:\d{2}(?:\.\d{3})?) ~ (\d{2}/\d{2}/\d{2} \d{2}:\d{2}:\d{2}(?:\.\d{3})?) (\d+) (\S+) (-?\d+)$"
+    )
+    .unwrap();
+    static ref ACCESS_LOG_NAME: Regex = Regex::new(r"^(.+)\.access$").unwrap();
+}
+
+/// A client session recorded in a broker access log.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessLogEntry {
+    /// Broker name, taken from the `<broker>.access` file name.
+    pub broker_name: Option<String>,
+    /// Number of the CAS that served the session.
+    pub cas_id: u32,
+    pub client_ip: String,
+    /// Client application name, `None` when logged as `-`.
+    pub client_app: Option<String>,
+    pub start_time: NaiveDateTime,
+    pub end_time: NaiveDateTime,
+    /// Process id of the CAS.
+    pub pid: u32,
+    /// Error message, `None` when logged as `-`.
+    pub error_message: Option<String>,
+    /// Error code, `None` when the session ended without error (`-1`).
+    pub error_code: Option<i32>,
+    /// Line number of the session in the access log.
+    pub source_line: u64,
+}
+
+impl AccessLogEntry {
+    /// Duration of the session in seconds.
+    pub fn elapsed(&self) -> f64 {
+        (self.end_time - self.start_time).num_milliseconds() as f64 / 1000.0
+    }
+}
+
+/// Returns the broker name of a standard `<broker>.access` file name.
+pub fn split_access_log_name(filename: &str) -> Option<&str> {
+    Some(ACCESS_LOG_NAME.captures(filename)?.get(1)?.as_str())
+}
+
+fn parse_access_time(text: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(text, "%y/%m/%d %H:%M:%S%.f").ok()
+}
+
+fn dash_to_none(text: &str) -> Option<String> {
+    (text != "-").then(|| text.to_string())
+}
+
+/// Parses one access log line. Returns `None` if the line is not a session.
+pub fn parse_access_line(line: &str) -> Option<AccessLogEntry> {
+    let caps = ACCESS_LINE.captures(line)?;
+    let error_code = caps[8].parse().ok()?;
+
+    Some(AccessLogEntry {
+        broker_name: None,
+        cas_id: caps[1].parse().ok()?,
+        client_ip: caps[2].to_string(),
+        client_app: dash_to_none(&caps[3]),
+        start_time: parse_access_time(&caps[4])?,
+        end_time: parse_access_time(&caps[5])?,
+        pid: caps[6].parse().ok()?,
+        error_message: dash_to_none(&caps[7]),
+        error_code: (error_code != -1).then_some(error_code),
+        source_line: 0,
+    })
+}
+
+/// Streaming parser over a broker access log (`log/broker/<broker>.access`).
+///
+/// Lines that are not sessions are skipped and reported as [`Diagnostic`]s.
+pub struct AccessLogEntries<R> {
+    lines: LineReader<R>,
+    line: String,
+    broker_name: Option<String>,
+    diagnostics: Vec<Diagnostic>,
+    finished: bool,
+}
+
+impl<R: BufRead> AccessLogEntries<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: LineReader::new(reader),
+            line: String::new(),
+            broker_name: None,
+            diagnostics: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// Sets the name of the access log file, from which the broker name is
+    /// taken when it follows the `<broker>.access` naming.
+    pub fn filename(mut self, filename: &str) -> Self {
+        self.broker_name = split_access_log_name(filename).map(str::to_string);
+        self
+    }
+
+    /// Sets how the input bytes are decoded.
+    pub fn decoding(mut self, decoding: Decoding) -> Self {
+        self.lines = self.lines.decoding(decoding);
+        self
+    }
+
+    /// Returns the diagnostics collected so far and clears them.
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+}
+
+impl<R: BufRead> Iterator for AccessLogEntries<R> {
+    type Item = Result<AccessLogEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        loop {
+            match self.lines.read_line(&mut self.line, &mut self.diagnostics) {
+                Ok(true) => {
+                    if let Some(mut entry) = parse_access_line(&self.line) {
+                        entry.broker_name = self.broker_name.clone();
+                        entry.source_line = self.lines.line_no();
+                        return Some(Ok(entry));
+                    }
+                    if !self.line.is_empty() {
+                        self.diagnostics.push(Diagnostic {
+                            kind: DiagnosticKind::UnrecognizedLine,
+                            line_no: self.lines.line_no(),
+                            byte_offset: self.lines.line_offset(),
+                            text: self.line.clone(),
+                            query_no: None,
+                            message: "Unrecognized access log line".to_string(),
+                        });
+                    }
+                }
+                Ok(false) => {
+                    self.finished = true;
+                    return None;
+                }
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACCESS_LOG: &str = "\
+1 192.168.1.203 - 21/02/24 15:30:40 ~ 21/02/24 15:30:50 2580 - -1
+2 192.168.1.204 java 21/02/24 15:30:41.250 ~ 21/02/24 15:30:41.750 2581 ERR_TIMEOUT -1012
+garbage
+";
+
+    fn time(text: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f").unwrap()
+    }
+
+    #[test]
+    fn test_parse_access_log() -> Result<()> {
+        let mut parser = AccessLogEntries::new(ACCESS_LOG.as_bytes()).filename("broker1.access");
+        let entries = parser.by_ref().collect::<Result<Vec<_>>>()?;
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].broker_name.as_deref(), Some("broker1"));
+        assert_eq!(entries[0].cas_id, 1);
+        assert_eq!(entries[0].client_ip, "192.168.1.203");
+        assert_eq!(entries[0].client_app, None);
+        assert_eq!(entries[0].start_time, time("2021-02-24 15:30:40"));
+        assert_eq!(entries[0].end_time, time("2021-02-24 15:30:50"));
+        assert_eq!(entries[0].elapsed(), 10.0);
+        assert_eq!(entries[0].pid, 2580);
+        assert_eq!(entries[0].error_code, None);
+        assert_eq!(entries[0].source_line, 1);
+
+        assert_eq!(entries[1].client_app.as_deref(), Some("java"));
+        assert_eq!(entries[1].start_time, time("2021-02-24 15:30:41.250"));
+        assert_eq!(entries[1].elapsed(), 0.5);
+        assert_eq!(entries[1].error_message.as_deref(), Some("ERR_TIMEOUT"));
+        assert_eq!(entries[1].error_code, Some(-1012));
+
+        let diagnostics = parser.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line_no, 3);
+        assert_eq!(diagnostics[0].text, "garbage");
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_access_log_name() {
+        assert_eq!(split_access_log_name("broker1.access"), Some("broker1"));
+        assert_eq!(
+            split_access_log_name("query_editor.access"),
+            Some("query_editor")
+        );
+        assert_eq!(split_access_log_name("broker1.err"), None);
+    }
+}
@@ -0,0 +1,300 @@
+use std::fmt;
+
+/// CUBRID type of a bind variable, as written by the broker in front of the value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindType {
+    Null,
+    Char,
+    Varchar,
+    Nchar,
+    Varnchar,
+    Bit,
+    Varbit,
+    Numeric,
+    Int,
+    Short,
+    Monetary,
+    Float,
+    Double,
+    Date,
+    Time,
+    Timestamp,
+    Set,
+    Multiset,
+    Sequence,
+    Object,
+    Bigint,
+    Datetime,
+    Blob,
+    Clob,
+    Enum,
+    Ushort,
+    Uint,
+    Ubigint,
+    TimestampTz,
+    TimestampLtz,
+    DatetimeTz,
+    DatetimeLtz,
+    Json,
+    /// A bind whose text could not be parsed, kept as is in lenient mode.
+    Unparsed,
+}
+
+impl BindType {
+    /// All types, in the order of CUBRID's `T_CCI_U_TYPE`.
+    pub const ALL: [BindType; 33] = [
+        BindType::Null,
+        BindType::Char,
+        BindType::Varchar,
+        BindType::Nchar,
+        BindType::Varnchar,
+        BindType::Bit,
+        BindType::Varbit,
+        BindType::Numeric,
+        BindType::Int,
+        BindType::Short,
+        BindType::Monetary,
+        BindType::Float,
+        BindType::Double,
+        BindType::Date,
+        BindType::Time,
+        BindType::Timestamp,
+        BindType::Set,
+        BindType::Multiset,
+        BindType::Sequence,
+        BindType::Object,
+        BindType::Bigint,
+        BindType::Datetime,
+        BindType::Blob,
+        BindType::Clob,
+        BindType::Enum,
+        BindType::Ushort,
+        BindType::Uint,
+        BindType::Ubigint,
+        BindType::TimestampTz,
+        BindType::TimestampLtz,
+        BindType::DatetimeTz,
+        BindType::DatetimeLtz,
+        BindType::Json,
+    ];
+
+    /// Name of the type as it appears in the log.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BindType::Null => "NULL",
+            BindType::Char => "CHAR",
+            BindType::Varchar => "VARCHAR",
+            BindType::Nchar => "NCHAR",
+            BindType::Varnchar => "VARNCHAR",
+            BindType::Bit => "BIT",
+            BindType::Varbit => "VARBIT",
+            BindType::Numeric => "NUMERIC",
+            BindType::Int => "INT",
+            BindType::Short => "SHORT",
+            BindType::Monetary => "MONETARY",
+            BindType::Float => "FLOAT",
+            BindType::Double => "DOUBLE",
+            BindType::Date => "DATE",
+            BindType::Time => "TIME",
+            BindType::Timestamp => "TIMESTAMP",
+            BindType::Set => "SET",
+            BindType::Multiset => "MULTISET",
+            BindType::Sequence => "SEQUENCE",
+            BindType::Object => "OBJECT",
+            BindType::Bigint => "BIGINT",
+            BindType::Datetime => "DATETIME",
+            BindType::Blob => "BLOB",
+            BindType::Clob => "CLOB",
+            BindType::Enum => "ENUM",
+            BindType::Ushort => "USHORT",
+            BindType::Uint => "UINT",
+            BindType::Ubigint => "UBIGINT",
+            BindType::TimestampTz => "TIMESTAMPTZ",
+            BindType::TimestampLtz => "TIMESTAMPLTZ",
+            BindType::DatetimeTz => "DATETIMETZ",
+            BindType::DatetimeLtz => "DATETIMELTZ",
+            BindType::Json => "JSON",
+            BindType::Unparsed => "UNPARSED",
+        }
+    }
+
+    /// Looks up a type by the name written in the log.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.name() == name)
+    }
+
+    /// Types written as `(<length>)<value>`, where the length counts the
+    /// value bytes plus the terminating NUL.
+    pub fn is_character_string(&self) -> bool {
+        matches!(
+            self,
+            BindType::Char
+                | BindType::Varchar
+                | BindType::Nchar
+                | BindType::Varnchar
+                | BindType::Enum
+                | BindType::Json
+        )
+    }
+
+    /// Types written as `(<length>)<hex digits>`.
+    pub fn is_bit_string(&self) -> bool {
+        matches!(self, BindType::Bit | BindType::Varbit)
+    }
+
+    /// Types written as `(<count>) {<element>, ...}`.
+    pub fn is_collection(&self) -> bool {
+        matches!(
+            self,
+            BindType::Set | BindType::Multiset | BindType::Sequence
+        )
+    }
+}
+
+impl fmt::Display for BindType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A bind variable together with its declared CUBRID type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BindValue {
+    pub bind_type: BindType,
+    /// Length declared in parentheses for character and bit string types,
+    /// e.g. `VARCHAR (11)`.
+    pub length: Option<usize>,
+    /// Value text as written in the log. `NULL` binds hold `"NULL"` and
+    /// collections hold the whole `{...}` text.
+    pub value: String,
+    /// Elements of a collection bind. Empty for every other type.
+    pub elements: Vec<BindValue>,
+    /// Whether the value holds fewer bytes than its declared length, which
+    /// happens when the log was cut in the middle of it.
+    pub truncated: bool,
+}
+
+impl BindValue {
+    pub fn new(bind_type: BindType, length: Option<usize>, value: impl Into<String>) -> Self {
+        Self {
+            bind_type,
+            length,
+            value: value.into(),
+            elements: Vec::new(),
+            truncated: false,
+        }
+    }
+
+    pub fn null() -> Self {
+        Self::new(BindType::Null, None, "NULL")
+    }
+
+    /// Renders the value as a CUBRID literal of its type, which can be
+    /// pasted into a query run by csql:
+    /// - numbers as they are
+    /// - character strings quoted, with `'` doubled, and national ones as
+    ///   `N'...'`
+    /// - bit strings, logged as hex digits, as `X'...'`
+    /// - dates and times as typed literals such as `DATE'2021-02-24'`
+    /// - collections as `SET{...}`, `MULTISET{...}` or `SEQUENCE{...}` of
+    ///   their rendered elements
+    ///
+    /// LOB locators and OIDs, which have no literal form, are rendered as
+    /// strings. An unparsed bind is kept as logged.
+    pub fn to_literal(&self) -> String {
+        match self.bind_type {
+            BindType::Null => "NULL".to_string(),
+            BindType::Numeric
+            | BindType::Int
+            | BindType::Short
+            | BindType::Monetary
+            | BindType::Float
+            | BindType::Double
+            | BindType::Bigint
+            | BindType::Ushort
+            | BindType::Uint
+            | BindType::Ubigint
+            | BindType::Unparsed => self.value.clone(),
+            BindType::Nchar | BindType::Varnchar => format!("N{}", quote(&self.value)),
+            BindType::Bit | BindType::Varbit => format!("X'{}'", self.value),
+            BindType::Date
+            | BindType::Time
+            | BindType::Timestamp
+            | BindType::Datetime
+            | BindType::TimestampTz
+            | BindType::TimestampLtz
+            | BindType::DatetimeTz
+            | BindType::DatetimeLtz => format!("{}{}", self.bind_type, quote(&self.value)),
+            BindType::Set | BindType::Multiset | BindType::Sequence => {
+                let elements: Vec<String> = self.elements.iter().map(|e| e.to_literal()).collect();
+                format!("{}{{{}}}", self.bind_type, elements.join(", "))
+            }
+            BindType::Char
+            | BindType::Varchar
+            | BindType::Enum
+            | BindType::Json
+            | BindType::Object
+            | BindType::Blob
+            | BindType::Clob => quote(&self.value),
+        }
+    }
+}
+
+/// Quotes `text` as a string literal.
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+impl fmt::Display for BindValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.value)
+    }
+}
+
+/// Compares only the value text, which keeps assertions on parsed binds short.
+impl PartialEq<&str> for BindValue {
+    fn eq(&self, other: &&str) -> bool {
+        self.value == *other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn literal(bind_type: BindType, value: &str) -> String {
+        BindValue::new(bind_type, None, value).to_literal()
+    }
+
+    #[test]
+    fn test_to_literal() {
+        assert_eq!(BindValue::null().to_literal(), "NULL");
+        assert_eq!(literal(BindType::Int, "-42"), "-42");
+        assert_eq!(literal(BindType::Double, "1.5e3"), "1.5e3");
+        assert_eq!(literal(BindType::Varchar, "O'Brien"), "'O''Brien'");
+        assert_eq!(literal(BindType::Char, "abc"), "'abc'");
+        assert_eq!(literal(BindType::Varnchar, "홍길동"), "N'홍길동'");
+        assert_eq!(literal(BindType::Varbit, "0a1b"), "X'0a1b'");
+        assert_eq!(literal(BindType::Date, "2021-02-24"), "DATE'2021-02-24'");
+        assert_eq!(
+            literal(BindType::Timestamp, "2021-02-24 15:30:45"),
+            "TIMESTAMP'2021-02-24 15:30:45'"
+        );
+        assert_eq!(
+            literal(BindType::DatetimeTz, "2021-02-24 15:30:45.123 +09:00"),
+            "DATETIMETZ'2021-02-24 15:30:45.123 +09:00'"
+        );
+        assert_eq!(literal(BindType::Unparsed, "RESULTSET 3"), "RESULTSET 3");
+    }
+
+    #[test]
+    fn test_collection_literal() {
+        let mut set = BindValue::new(BindType::Sequence, None, "{1, 'a', NULL}");
+        set.elements = vec![
+            BindValue::new(BindType::Int, None, "1"),
+            BindValue::new(BindType::Varchar, Some(2), "it's"),
+            BindValue::null(),
+        ];
+        assert_eq!(set.to_literal(), "SEQUENCE{1, 'it''s', NULL}");
+    }
+}
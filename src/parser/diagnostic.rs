@@ -0,0 +1,68 @@
+use std::fmt;
+
+use anyhow::Result;
+
+use super::{LogEntry, Transaction};
+
+/// Category of a problem found while parsing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// A line that matches none of the known line formats.
+    UnrecognizedLine,
+    /// A bind line whose value could not be parsed.
+    InvalidBind,
+    /// A string bind value holding fewer bytes than its declared length.
+    TruncatedBind,
+    /// A line with bytes invalid in the input encoding, replaced in lossy mode.
+    InvalidEncoding,
+}
+
+impl DiagnosticKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DiagnosticKind::UnrecognizedLine => "unrecognized_line",
+            DiagnosticKind::InvalidBind => "invalid_bind",
+            DiagnosticKind::TruncatedBind => "truncated_bind",
+            DiagnosticKind::InvalidEncoding => "invalid_encoding",
+        }
+    }
+}
+
+impl fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A problem found in the input, located by line number and byte offset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    /// 1-based line number in the input.
+    pub line_no: u64,
+    /// Byte offset of the start of the line in the input.
+    pub byte_offset: u64,
+    /// The offending text.
+    pub text: String,
+    /// Number of the `[Qn]` block the line belongs to, if any.
+    pub query_no: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}: {}", self.line_no, self.kind, self.message)
+    }
+}
+
+/// An iterator of parsed entries that also reports diagnostics.
+pub trait EntryStream: Iterator<Item = Result<LogEntry>> {
+    /// Returns the diagnostics collected so far and clears them.
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic>;
+
+    /// Returns the transactions completed so far and clears them. Only
+    /// formats that record transaction boundaries have any.
+    fn take_transactions(&mut self) -> Vec<Transaction> {
+        Vec::new()
+    }
+}
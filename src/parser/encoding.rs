@@ -0,0 +1,209 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Result};
+use chardetng::{EncodingDetector, Iso2022JpDetection, Utf8Detection};
+use encoding_rs::{Encoding, UTF_8};
+
+/// Character encoding of a log input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputEncoding {
+    /// Lines that are valid UTF-8 are read as such. The encoding of the
+    /// others is detected once per input, from those among its first 64 KiB
+    /// or else from the first of them, and used for all of them.
+    #[default]
+    Auto,
+    Fixed(&'static Encoding),
+}
+
+impl FromStr for InputEncoding {
+    type Err = anyhow::Error;
+
+    /// Accepts `auto` or the WHATWG label of an ASCII-compatible encoding,
+    /// e.g. `euc-kr` or `utf-8`. `cp949`, which WHATWG only knows as
+    /// `windows-949`, is accepted as well.
+    fn from_str(label: &str) -> Result<Self> {
+        if label.eq_ignore_ascii_case("auto") {
+            return Ok(InputEncoding::Auto);
+        }
+        let whatwg_label = if label.eq_ignore_ascii_case("cp949") {
+            "windows-949"
+        } else {
+            label
+        };
+        let encoding = Encoding::for_label(whatwg_label.as_bytes())
+            .ok_or_else(|| anyhow!("Unknown encoding: {}", label))?;
+        // Lines are split on b'\n' before decoding.
+        if !encoding.is_ascii_compatible() {
+            bail!("Unsupported encoding: {}", encoding.name());
+        }
+        Ok(InputEncoding::Fixed(encoding))
+    }
+}
+
+impl fmt::Display for InputEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputEncoding::Auto => f.write_str("auto"),
+            InputEncoding::Fixed(encoding) => f.write_str(encoding.name()),
+        }
+    }
+}
+
+/// How the bytes of a log input are turned into text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Decoding {
+    pub encoding: InputEncoding,
+    /// Replaces undecodable bytes with U+FFFD instead of failing.
+    pub lossy: bool,
+}
+
+/// Number of bytes at the start of an input, and of the lines that are not
+/// valid UTF-8 among them, from which its encoding is detected.
+pub(crate) const DETECTION_SAMPLE_LEN: usize = 64 * 1024;
+
+/// Detects the encoding of the lines of `bytes` that are not valid UTF-8,
+/// from up to [`DETECTION_SAMPLE_LEN`] bytes of them. Returns `None` if
+/// there are none.
+pub(crate) fn detect_encoding(bytes: &[u8]) -> Option<&'static Encoding> {
+    let mut detector = EncodingDetector::new(Iso2022JpDetection::Deny);
+    let mut sampled = 0;
+    for line in bytes.split(|&b| b == b'\n') {
+        if sampled >= DETECTION_SAMPLE_LEN {
+            break;
+        }
+        if std::str::from_utf8(line).is_err() {
+            let line = &line[..line.len().min(DETECTION_SAMPLE_LEN - sampled)];
+            detector.feed(line, false);
+            sampled += line.len();
+        }
+    }
+    (sampled > 0).then(|| {
+        detector.feed(&[], true);
+        detector.guess(None, Utf8Detection::Deny)
+    })
+}
+
+/// Decodes the lines of one input, remembering the detected encoding.
+pub(crate) struct LineDecoder {
+    decoding: Decoding,
+    detected: Option<&'static Encoding>,
+}
+
+impl LineDecoder {
+    pub fn new(decoding: Decoding) -> Self {
+        Self {
+            decoding,
+            detected: None,
+        }
+    }
+
+    /// Uses `encoding`, detected beforehand for the whole input, for the
+    /// lines that are not valid UTF-8 in auto mode. Without one, it is
+    /// detected from the first such line.
+    pub fn detected(mut self, encoding: Option<&'static Encoding>) -> Self {
+        self.detected = encoding;
+        self
+    }
+
+    pub fn detected_encoding(&self) -> Option<&'static Encoding> {
+        self.detected
+    }
+
+    fn encoding_for(&mut self, bytes: &[u8]) -> &'static Encoding {
+        match self.decoding.encoding {
+            InputEncoding::Fixed(encoding) => encoding,
+            InputEncoding::Auto if std::str::from_utf8(bytes).is_ok() => UTF_8,
+            InputEncoding::Auto => self
+                .detected
+                .get_or_insert_with(|| detect_encoding(bytes).unwrap_or(UTF_8)),
+        }
+    }
+
+    /// Decodes `bytes` into `line`. Returns `true` if undecodable bytes were
+    /// replaced, which only happens in lossy mode.
+    pub fn decode(&mut self, bytes: &[u8], line: &mut String) -> Result<bool> {
+        let encoding = self.encoding_for(bytes);
+        let (text, had_errors) = encoding.decode_without_bom_handling(bytes);
+        if had_errors && !self.decoding.lossy {
+            bail!("Invalid {} data", encoding.name());
+        }
+        line.push_str(&text);
+        Ok(had_errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::EUC_KR;
+
+    fn decode(decoding: Decoding, bytes: &[u8]) -> Result<(String, bool)> {
+        let mut line = String::new();
+        let replaced = LineDecoder::new(decoding).decode(bytes, &mut line)?;
+        Ok((line, replaced))
+    }
+
+    #[test]
+    fn test_parse_encoding() -> Result<()> {
+        assert_eq!("auto".parse::<InputEncoding>()?, InputEncoding::Auto);
+        assert_eq!(
+            "cp949".parse::<InputEncoding>()?,
+            InputEncoding::Fixed(EUC_KR)
+        );
+        assert!("klingon".parse::<InputEncoding>().is_err());
+        assert!("utf-16le".parse::<InputEncoding>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_euc_kr() -> Result<()> {
+        let (bytes, _, _) = EUC_KR.encode("SELECT '한글 데이터베이스 테스트'");
+
+        let fixed = Decoding {
+            encoding: InputEncoding::Fixed(EUC_KR),
+            lossy: false,
+        };
+        assert_eq!(
+            decode(fixed, &bytes)?,
+            ("SELECT '한글 데이터베이스 테스트'".to_string(), false)
+        );
+        assert_eq!(
+            decode(Decoding::default(), &bytes)?,
+            ("SELECT '한글 데이터베이스 테스트'".to_string(), false)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_encoding() {
+        let (korean, _, _) = EUC_KR.encode("SELECT '한글 데이터베이스 테스트'\n");
+        let mut bytes = b"SELECT 1\n".to_vec();
+        bytes.extend_from_slice(&korean);
+
+        assert_eq!(detect_encoding(&bytes), Some(EUC_KR));
+        assert_eq!(detect_encoding(b"SELECT 1\nSELECT '\xc3\xa9'\n"), None);
+    }
+
+    #[test]
+    fn test_decode_invalid_bytes() -> Result<()> {
+        let bytes = b"SELECT '\xff'";
+        let utf8 = Decoding {
+            encoding: InputEncoding::Fixed(UTF_8),
+            lossy: false,
+        };
+
+        assert!(decode(utf8, bytes).is_err());
+        assert_eq!(
+            decode(
+                Decoding {
+                    lossy: true,
+                    ..utf8
+                },
+                bytes
+            )?,
+            ("SELECT '\u{fffd}'".to_string(), true)
+        );
+        Ok(())
+    }
+}
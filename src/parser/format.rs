@@ -0,0 +1,205 @@
+use std::io::{BufRead, Cursor, Read};
+
+use anyhow::Result;
+
+use super::{EntryStream, LogParser};
+
+/// Number of bytes at the start of an input used to detect its format.
+pub const SNIFF_LEN: u64 = 8 * 1024;
+
+/// A log format whose records are read as [`LogEntry`](super::LogEntry)s.
+pub trait LogFormat: Sync {
+    /// Name of the format on the command line, e.g. `log-top`.
+    fn name(&self) -> &'static str;
+
+    /// One-line description of the format, shown in the command line help.
+    fn description(&self) -> &'static str;
+
+    /// Whether `sample`, the start of an input, is written in this format,
+    /// as recognized by the line patterns of `parser`. The last line of the
+    /// sample may be cut.
+    fn detect(&self, parser: &LogParser, sample: &str) -> bool;
+
+    /// Parses `reader` with the settings of `parser`. `filename` is the name
+    /// of the input file, or empty for stdin.
+    fn parse<'a>(
+        &self,
+        parser: &LogParser,
+        reader: Box<dyn BufRead + 'a>,
+        filename: &str,
+    ) -> Result<Box<dyn EntryStream + 'a>>;
+}
+
+/// Output of `broker_log_top` (`log_top.q`), made of `[Qn]-----` blocks.
+pub struct LogTopFormat;
+
+impl LogFormat for LogTopFormat {
+    fn name(&self) -> &'static str {
+        "log-top"
+    }
+
+    fn description(&self) -> &'static str {
+        "Output of `broker_log_top` (`log_top.q`)"
+    }
+
+    fn detect(&self, parser: &LogParser, sample: &str) -> bool {
+        let patterns = parser.patterns();
+        sample
+            .lines()
+            .any(|line| patterns.re_query_no.is_match(line.trim_end_matches('\r')))
+    }
+
+    fn parse<'a>(
+        &self,
+        parser: &LogParser,
+        reader: Box<dyn BufRead + 'a>,
+        _filename: &str,
+    ) -> Result<Box<dyn EntryStream + 'a>> {
+        parser.parse(reader)
+    }
+}
+
+/// Raw CAS SQL log (`sql_log/<broker>_<cas>.sql.log`).
+pub struct SqlLogFormat;
+
+impl LogFormat for SqlLogFormat {
+    fn name(&self) -> &'static str {
+        "sql-log"
+    }
+
+    fn description(&self) -> &'static str {
+        "Raw CAS SQL log (`sql_log/<broker>_<cas>.sql.log`)"
+    }
+
+    /// Accepts any input with a timestamped line, so it must be tried after
+    /// [`LogTopFormat`], whose blocks contain the same lines.
+    fn detect(&self, parser: &LogParser, sample: &str) -> bool {
+        let patterns = parser.patterns();
+        sample
+            .lines()
+            .any(|line| patterns.parse_timestamped(line).is_some())
+    }
+
+    fn parse<'a>(
+        &self,
+        parser: &LogParser,
+        reader: Box<dyn BufRead + 'a>,
+        filename: &str,
+    ) -> Result<Box<dyn EntryStream + 'a>> {
+        parser.parse_sql_log(reader, filename)
+    }
+}
+
+/// Known formats, in the order in which detection tries them.
+pub const LOG_FORMATS: &[&dyn LogFormat] = &[&LogTopFormat, &SqlLogFormat];
+
+/// Returns the format named `name`, e.g. `sql-log`.
+pub fn format_by_name(name: &str) -> Option<&'static dyn LogFormat> {
+    LOG_FORMATS
+        .iter()
+        .copied()
+        .find(|format| format.name() == name)
+}
+
+/// Detects the format of an input from `sample`, its first bytes, with the
+/// line patterns of `parser`.
+pub fn detect_format(parser: &LogParser, sample: &[u8]) -> Option<&'static dyn LogFormat> {
+    // The markers of every format are ASCII, so the encoding of the rest of
+    // the line does not matter.
+    let sample = String::from_utf8_lossy(sample);
+    LOG_FORMATS
+        .iter()
+        .copied()
+        .find(|format| format.detect(parser, &sample))
+}
+
+/// Reads the first [`SNIFF_LEN`] bytes of `reader`. Returns them with a reader
+/// that yields the whole input again.
+pub fn sniff<'a, R: BufRead + 'a>(reader: R) -> Result<(Vec<u8>, Box<dyn BufRead + 'a>)> {
+    peek(reader, SNIFF_LEN as usize)
+}
+
+/// Reads the first `len` bytes of `reader`. Returns them with a reader that
+/// yields the whole input again.
+pub(crate) fn peek<'a, R: BufRead + 'a>(
+    mut reader: R,
+    len: usize,
+) -> Result<(Vec<u8>, Box<dyn BufRead + 'a>)> {
+    let mut sample = Vec::new();
+    reader.by_ref().take(len as u64).read_to_end(&mut sample)?;
+    let replay = Cursor::new(sample.clone()).chain(reader);
+    Ok((sample, Box::new(replay)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG_TOP: &str = "[Q1]-------------------------------------------\n\
+broker1_1.sql.log:23\n\
+21-02-24 15:30:45.123 (1) execute srv_h_id 1 SELECT 1\n";
+
+    const SQL_LOG: &str = "21-02-24 15:30:45.100 (0) CLIENT IP 127.0.0.1\n\
+21-02-24 15:30:45.123 (1) execute srv_h_id 1 SELECT 1\n";
+
+    #[test]
+    fn test_detect_format() {
+        let parser = LogParser::default();
+        let name =
+            |sample: &str| detect_format(&parser, sample.as_bytes()).map(|format| format.name());
+
+        assert_eq!(name(LOG_TOP), Some("log-top"));
+        assert_eq!(name(&LOG_TOP.replace('\n', "\r\n")), Some("log-top"));
+        assert_eq!(name(SQL_LOG), Some("sql-log"));
+        assert_eq!(
+            name("09/24 15:30:45.123 (1) execute srv_h_id 1 SELECT 1\n"),
+            Some("sql-log")
+        );
+        assert_eq!(name("[Q1]  0.012  0.001  0.004  10  0\n"), None);
+        assert_eq!(name(""), None);
+    }
+
+    #[test]
+    fn test_detect_with_parser_patterns() -> Result<()> {
+        // A site that prefixes every block header with its host name.
+        let parser = LogParser::builder()
+            .query_no_pattern(r"^db1 \[Q(\d+)\]-+$")
+            .build()?;
+        let sample = LOG_TOP.replace("[Q1]", "db1 [Q1]");
+
+        let format = detect_format(&parser, sample.as_bytes()).map(|format| format.name());
+        assert_eq!(format, Some("log-top"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_by_name() {
+        assert_eq!(format_by_name("sql-log").map(|f| f.name()), Some("sql-log"));
+        assert!(format_by_name("slow-log").is_none());
+    }
+
+    #[test]
+    fn test_sniff_replays_input() -> Result<()> {
+        let input = SQL_LOG.repeat(200);
+        let (sample, mut reader) = sniff(input.as_bytes())?;
+
+        assert_eq!(sample.len() as u64, SNIFF_LEN);
+        let mut replayed = String::new();
+        reader.read_to_string(&mut replayed)?;
+        assert_eq!(replayed, input);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_detected_format() -> Result<()> {
+        let (sample, reader) = sniff(SQL_LOG.as_bytes())?;
+        let format = detect_format(&LogParser::default(), &sample).unwrap();
+
+        let entries = format
+            .parse(&LogParser::default(), reader, "broker1_2.sql.log")?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].cas_id, Some(2));
+        Ok(())
+    }
+}
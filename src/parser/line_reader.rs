@@ -0,0 +1,159 @@
+use std::collections::VecDeque;
+use std::io::BufRead;
+
+use anyhow::{anyhow, Result};
+use encoding_rs::Encoding;
+
+use super::encoding::{Decoding, LineDecoder};
+use super::{Diagnostic, DiagnosticKind};
+
+/// A line already read, together with its position in the input.
+#[derive(Debug, Clone)]
+pub(crate) struct BufferedLine {
+    pub text: String,
+    pub line_no: u64,
+    pub line_offset: u64,
+    /// Length in bytes of the line in the input, without its terminator.
+    pub len: usize,
+}
+
+/// Reads lines from a log input while keeping track of their position.
+pub(crate) struct LineReader<R> {
+    reader: R,
+    decoder: LineDecoder,
+    buf: Vec<u8>,
+    /// Line number of the last line read.
+    line_no: u64,
+    /// Byte offset of the start of the last line read.
+    line_offset: u64,
+    /// Length in bytes of the last line read, without its terminator.
+    line_len: usize,
+    /// Number of lines read from the input, which is more than `line_no`
+    /// while unread lines are read again.
+    lines_read: u64,
+    /// Byte offset of the next line to read.
+    next_offset: u64,
+    /// Lines handed back by [`unread`](Self::unread), read before the input.
+    unread: VecDeque<BufferedLine>,
+}
+
+impl<R> LineReader<R> {
+    pub fn line_no(&self) -> u64 {
+        self.line_no
+    }
+
+    pub fn line_offset(&self) -> u64 {
+        self.line_offset
+    }
+
+    pub fn line_len(&self) -> usize {
+        self.line_len
+    }
+
+    /// Returns the last line read, whose text is `text`, with its position.
+    pub fn last_line(&self, text: &str) -> BufferedLine {
+        BufferedLine {
+            text: text.to_string(),
+            line_no: self.line_no,
+            line_offset: self.line_offset,
+            len: self.line_len,
+        }
+    }
+
+    /// Hands `lines` back, so that they are read again, in order, before the
+    /// rest of the input.
+    pub fn unread(&mut self, lines: Vec<BufferedLine>) {
+        for line in lines.into_iter().rev() {
+            self.unread.push_front(line);
+        }
+    }
+}
+
+impl<R: BufRead> LineReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            decoder: LineDecoder::new(Decoding::default()),
+            buf: Vec::new(),
+            line_no: 0,
+            line_offset: 0,
+            line_len: 0,
+            lines_read: 0,
+            next_offset: 0,
+            unread: VecDeque::new(),
+        }
+    }
+
+    /// Sets the position of the reader within the whole input, so that
+    /// diagnostics for a part of a file point at the right place.
+    pub fn starting_at(mut self, lines_before: u64, byte_offset: u64) -> Self {
+        self.line_no = lines_before;
+        self.lines_read = lines_before;
+        self.next_offset = byte_offset;
+        self
+    }
+
+    pub fn decoding(mut self, decoding: Decoding) -> Self {
+        self.decoder = LineDecoder::new(decoding).detected(self.decoder.detected_encoding());
+        self
+    }
+
+    /// Sets the encoding detected for the input, see [`LineDecoder::detected`].
+    pub fn detected_encoding(mut self, encoding: Option<&'static Encoding>) -> Self {
+        self.decoder = self.decoder.detected(encoding);
+        self
+    }
+
+    /// Reads the next line into `line`, stripping the line terminator.
+    /// Returns `false` at end of input.
+    ///
+    /// A line whose undecodable bytes were replaced in lossy mode is reported
+    /// in `diagnostics`.
+    pub fn read_line(
+        &mut self,
+        line: &mut String,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Result<bool> {
+        line.clear();
+        if let Some(unread) = self.unread.pop_front() {
+            line.push_str(&unread.text);
+            self.line_no = unread.line_no;
+            self.line_offset = unread.line_offset;
+            self.line_len = unread.len;
+            return Ok(true);
+        }
+
+        self.buf.clear();
+        let read = self.reader.read_until(b'\n', &mut self.buf)?;
+        if read == 0 {
+            return Ok(false);
+        }
+        self.lines_read += 1;
+        self.line_no = self.lines_read;
+        self.line_offset = self.next_offset;
+        self.next_offset += read as u64;
+        if self.buf.ends_with(b"\n") {
+            self.buf.pop();
+            if self.buf.ends_with(b"\r") {
+                self.buf.pop();
+            }
+        }
+        self.line_len = self.buf.len();
+
+        let replaced = self
+            .decoder
+            .decode(&self.buf, line)
+            .map_err(|e| anyhow!("line {}: {}", self.line_no, e))?;
+        if replaced {
+            diagnostics.push(Diagnostic {
+                kind: DiagnosticKind::InvalidEncoding,
+                line_no: self.line_no,
+                byte_offset: self.line_offset,
+                text: line.clone(),
+                query_no: None,
+                message: "Undecodable bytes replaced".to_string(),
+            });
+        }
+        Ok(true)
+    }
+}
@@ -1,4 +1,6 @@
+use super::{count_parameters, find_placeholders, BindValue, Placeholder, PlaceholderStyle};
 use anyhow::Result;
+use chrono::NaiveDateTime;
 use std::fmt;
 
 #[derive(Default, Clone)]
@@ -6,37 +8,281 @@ pub struct LogEntry {
     pub query_no: String,
     pub filename: String,
     pub query: String,
-    pub bind_statements: Vec<String>,
+    pub bind_statements: Vec<BindValue>,
+    /// Verb of the `execute` line that started the execution.
+    pub execute_kind: ExecuteKind,
+    /// Time of the `execute srv_h_id` line.
+    pub start_time: Option<NaiveDateTime>,
+    /// Time of the `execute ... tuple ... time` line that ends the execution.
+    pub end_time: Option<NaiveDateTime>,
+    /// CAS process id written next to the timestamps.
+    pub pid: Option<u32>,
+    /// CUBRID error code when the execution failed.
+    pub error_code: Option<i32>,
+    /// Number of tuples reported by the end line.
+    pub tuple_count: Option<u64>,
+    /// Execution time in seconds.
+    pub elapsed: Option<f64>,
+    /// Server handle id from `execute srv_h_id N`.
+    pub srv_h_id: Option<u32>,
+    /// Line number in the CAS sql.log named by `filename`.
+    pub source_line: Option<u32>,
+    /// Broker name, when `filename` follows the `<broker>_<cas>.sql.log` naming.
+    pub broker_name: Option<String>,
+    /// CAS number, when `filename` follows the `<broker>_<cas>.sql.log` naming.
+    pub cas_id: Option<u32>,
+    /// Set when a bind value could not be parsed in lenient mode.
+    pub parse_error: Option<String>,
+    /// Number of the [`Transaction`](super::Transaction) the execution belongs
+    /// to, for inputs that record transaction boundaries.
+    pub transaction_no: Option<u64>,
 }
 
+/// Verb of an execute line, which tells how the statement was executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExecuteKind {
+    #[default]
+    Execute,
+    /// `execute_all`: a prepared statement executed once per parameter set,
+    /// as by a JDBC `executeBatch`.
+    ExecuteAll,
+    /// `execute_batch`: a batch of statements, written by CUBRID 11.
+    ExecuteBatch,
+    /// `execute_call`: a stored procedure call, written by CUBRID 11.
+    ExecuteCall,
+}
+
+impl ExecuteKind {
+    /// Parses the verb of an execute line. Unknown verbs are taken for a
+    /// plain `execute`.
+    pub fn from_verb(verb: &str) -> Self {
+        match verb {
+            "execute_all" => ExecuteKind::ExecuteAll,
+            "execute_batch" => ExecuteKind::ExecuteBatch,
+            "execute_call" => ExecuteKind::ExecuteCall,
+            _ => ExecuteKind::Execute,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExecuteKind::Execute => "execute",
+            ExecuteKind::ExecuteAll => "execute_all",
+            ExecuteKind::ExecuteBatch => "execute_batch",
+            ExecuteKind::ExecuteCall => "execute_call",
+        }
+    }
+
+    /// Whether the binds of the execution may hold several parameter sets.
+    pub fn is_batch(&self) -> bool {
+        matches!(self, ExecuteKind::ExecuteAll | ExecuteKind::ExecuteBatch)
+    }
+}
+
+impl fmt::Display for ExecuteKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Why the binds of an entry do not match the placeholders of its query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MismatchCause {
+    /// The binds match the `?` of the query once those inside string
+    /// literals, quoted identifiers and comments are counted as well.
+    LiteralQuestionMark,
+    /// Fewer binds than placeholders: bind lines are missing from the log.
+    MissingBinds,
+    /// A multiple of the placeholders, like the parameter sets of a batch
+    /// execution, on a line not logged as one.
+    BatchExecution,
+    /// More binds than placeholders for no known reason.
+    ExtraBinds,
+}
+
+impl MismatchCause {
+    /// Works out why `bind_count` binds do not match `query`.
+    pub fn detect(query: &str, bind_count: usize) -> Self {
+        Self::detect_with(query, &find_placeholders(query), bind_count)
+    }
+
+    /// Like [`detect`](Self::detect), with the placeholders of `query`.
+    pub(crate) fn detect_with(
+        query: &str,
+        placeholders: &[Placeholder],
+        bind_count: usize,
+    ) -> Self {
+        let questions = placeholders
+            .iter()
+            .all(|p| p.style == PlaceholderStyle::Question);
+        let placeholders = count_parameters(placeholders);
+        if questions && query.matches('?').count() == bind_count {
+            MismatchCause::LiteralQuestionMark
+        } else if bind_count < placeholders {
+            MismatchCause::MissingBinds
+        } else if placeholders > 0 && bind_count.is_multiple_of(placeholders) {
+            MismatchCause::BatchExecution
+        } else {
+            MismatchCause::ExtraBinds
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MismatchCause::LiteralQuestionMark => "literal_question_mark",
+            MismatchCause::MissingBinds => "missing_binds",
+            MismatchCause::BatchExecution => "batch_execution",
+            MismatchCause::ExtraBinds => "extra_binds",
+        }
+    }
+}
+
+impl fmt::Display for MismatchCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Marks a placeholder left without a bind by [`LogEntry::rebind_query`].
+pub const UNBOUND_MARKER: &str = "/* unbound */";
+
 impl LogEntry {
-    pub fn replace_query_params(query: &str, bind_statements: &[String]) -> Result<String> {
-        // Split the query on '?' characters. For n placeholders, we expect n+1 parts.
-        let parts: Vec<&str> = query.split('?').collect();
+    /// Substitutes the bind values, rendered as literals of their type by
+    /// [`BindValue::to_literal`], for the placeholders of `query`: `?`, `$n`
+    /// or `:name`, whichever the query uses.
+    ///
+    /// Only real placeholders are replaced: one inside a string literal, a
+    /// quoted identifier or a comment is left as is. See [`find_placeholders`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are binds but not one per parameter.
+    pub fn replace_query_params(query: &str, bind_statements: &[BindValue]) -> Result<String> {
+        let placeholder_count = count_parameters(&find_placeholders(query));
 
-        // Validate that the number of '?' placeholders matches the number of bind parameters.
-        if parts.len() - 1 != bind_statements.len() && !bind_statements.is_empty() {
+        // Validate that the number of placeholders matches the number of bind parameters.
+        if placeholder_count != bind_statements.len() && !bind_statements.is_empty() {
             return Err(anyhow::anyhow!(
-                "Number of ? in query ({}) does not match number of bind parameters ({})",
-                parts.len() - 1,
+                "Number of parameters in query ({}) does not match number of bind parameters ({})",
+                placeholder_count,
                 bind_statements.len()
             ));
         }
+        Ok(Self::rebind_query(query, bind_statements))
+    }
+
+    /// Like [`replace_query_params`](Self::replace_query_params), but binds
+    /// as many placeholders as possible when the counts do not match.
+    ///
+    /// Placeholders without a bind are kept and followed by
+    /// [`UNBOUND_MARKER`]. Binds left over are listed in a trailing
+    /// `/* unused binds: ... */` comment.
+    pub fn rebind_query(query: &str, bind_statements: &[BindValue]) -> String {
+        if bind_statements.is_empty() {
+            return query.to_string();
+        }
+        Self::rebind_with(query, &find_placeholders(query), bind_statements)
+    }
+
+    /// Like [`rebind_query`](Self::rebind_query), with the placeholders of
+    /// `query`, so that they are found once for several sets of binds.
+    pub(crate) fn rebind_with(
+        query: &str,
+        placeholders: &[Placeholder],
+        bind_statements: &[BindValue],
+    ) -> String {
+        if bind_statements.is_empty() {
+            return query.to_string();
+        }
 
         // Pre-calculate capacity to avoid multiple allocations.
-        let additional_capacity: usize = bind_statements.iter().map(|s| s.len()).sum();
+        let literals: Vec<String> = bind_statements.iter().map(|b| b.to_literal()).collect();
+        let additional_capacity: usize = literals.iter().map(String::len).sum();
         let mut result = String::with_capacity(query.len() + additional_capacity);
 
-        // Interleave each part with the corresponding bind parameter.
-        // Note: parts.len() == bind_statements.len() + 1
-        for (part, value) in parts.iter().zip(bind_statements.iter()) {
-            result.push_str(part);
-            result.push_str(value);
+        // Interleave the text between placeholders with the bind parameters.
+        let mut used = vec![false; literals.len()];
+        let mut last = 0;
+        for placeholder in placeholders {
+            result.push_str(&query[last..placeholder.start]);
+            match literals.get(placeholder.index) {
+                Some(literal) => {
+                    result.push_str(literal);
+                    used[placeholder.index] = true;
+                }
+                None => {
+                    result.push_str(&query[placeholder.start..placeholder.end]);
+                    result.push_str(UNBOUND_MARKER);
+                }
+            }
+            last = placeholder.end;
         }
-        // Append the final part after the last placeholder.
-        result.push_str(parts.last().unwrap());
+        // Append the text after the last placeholder.
+        result.push_str(&query[last..]);
 
-        Ok(result)
+        let unused: Vec<&str> = literals
+            .iter()
+            .zip(&used)
+            .filter(|(_, &used)| !used)
+            .map(|(literal, _)| literal.as_str())
+            .collect();
+        if !unused.is_empty() {
+            let unused = unused.join(", ");
+            result.push_str(" /* unused binds: ");
+            // Keep a literal from closing the comment early.
+            result.push_str(&unused.replace("*/", "* /"));
+            result.push_str(" */");
+        }
+        result
+    }
+
+    /// Number of binds the placeholders of the query take. See
+    /// [`count_parameters`].
+    pub fn placeholder_count(&self) -> usize {
+        count_parameters(&find_placeholders(&self.query))
+    }
+
+    /// Splits the binds of a batch execution into its parameter sets, one
+    /// per execution of the statement, in order.
+    ///
+    /// Returns `None` if the entry is not a batch execution, see
+    /// [`ExecuteKind::is_batch`], or if its binds are not a whole number of
+    /// parameter sets.
+    pub fn parameter_sets(&self) -> Option<Vec<&[BindValue]>> {
+        self.parameter_sets_with(&find_placeholders(&self.query))
+    }
+
+    /// Like [`parameter_sets`](Self::parameter_sets), with the placeholders
+    /// of the query.
+    pub(crate) fn parameter_sets_with(
+        &self,
+        placeholders: &[Placeholder],
+    ) -> Option<Vec<&[BindValue]>> {
+        let set_len = count_parameters(placeholders);
+        let binds = &self.bind_statements;
+        let splits = self.execute_kind.is_batch()
+            && set_len > 0
+            && !binds.is_empty()
+            && binds.len().is_multiple_of(set_len);
+        splits.then(|| binds.chunks(set_len).collect())
+    }
+
+    /// Returns why the binds do not match the placeholders, or `None` if
+    /// they match. An entry without binds always matches, and so does a
+    /// batch execution whose binds split into
+    /// [`parameter_sets`](Self::parameter_sets).
+    pub fn bind_mismatch(&self) -> Option<MismatchCause> {
+        self.bind_mismatch_with(&find_placeholders(&self.query))
+    }
+
+    /// Like [`bind_mismatch`](Self::bind_mismatch), with the placeholders of
+    /// the query.
+    pub(crate) fn bind_mismatch_with(&self, placeholders: &[Placeholder]) -> Option<MismatchCause> {
+        let bind_count = self.bind_statements.len();
+        (bind_count > 0
+            && count_parameters(placeholders) != bind_count
+            && self.parameter_sets_with(placeholders).is_none())
+        .then(|| MismatchCause::detect_with(&self.query, placeholders, bind_count))
     }
 }
 
@@ -45,10 +291,144 @@ impl fmt::Debug for LogEntry {
         writeln!(f, "LogEntry {{")?;
         writeln!(f, "    query_no: {:?}", self.query_no)?;
         writeln!(f, "    filename: {:?}", self.filename)?;
+        writeln!(f, "    source_line: {:?}", self.source_line)?;
+        writeln!(f, "    broker_name: {:?}", self.broker_name)?;
+        writeln!(f, "    cas_id: {:?}", self.cas_id)?;
+        writeln!(f, "    srv_h_id: {:?}", self.srv_h_id)?;
         writeln!(f, "    query: {:?}", self.query)?;
+        writeln!(f, "    execute_kind: {:?}", self.execute_kind)?;
+        writeln!(f, "    start_time: {:?}", self.start_time)?;
+        writeln!(f, "    end_time: {:?}", self.end_time)?;
+        writeln!(f, "    pid: {:?}", self.pid)?;
+        writeln!(f, "    error_code: {:?}", self.error_code)?;
+        writeln!(f, "    tuple_count: {:?}", self.tuple_count)?;
+        writeln!(f, "    elapsed: {:?}", self.elapsed)?;
+        writeln!(f, "    parse_error: {:?}", self.parse_error)?;
+        writeln!(f, "    transaction_no: {:?}", self.transaction_no)?;
         writeln!(f, "    bind_statements: [")?;
-        writeln!(f, "        {}", self.bind_statements.join(",\n        "))?;
+        for bind in &self.bind_statements {
+            writeln!(f, "        {} {},", bind.bind_type, bind.value)?;
+        }
         writeln!(f, "    ]")?;
         write!(f, "}}")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::BindType;
+
+    fn ints(values: &[&str]) -> Vec<BindValue> {
+        values
+            .iter()
+            .map(|v| BindValue::new(BindType::Int, None, *v))
+            .collect()
+    }
+
+    #[test]
+    fn test_replace_query_params() -> Result<()> {
+        let binds = ints(&["42"]);
+
+        assert_eq!(
+            LogEntry::replace_query_params(
+                "SELECT '?', \"a?\" FROM t /* ? */ WHERE a = ?",
+                &binds
+            )?,
+            "SELECT '?', \"a?\" FROM t /* ? */ WHERE a = 42"
+        );
+        assert!(LogEntry::replace_query_params("SELECT ?, ?", &binds).is_err());
+        // Without binds the query is kept as is.
+        assert_eq!(LogEntry::replace_query_params("SELECT ?", &[])?, "SELECT ?");
+        Ok(())
+    }
+
+    #[test]
+    fn test_rebind_query() {
+        assert_eq!(
+            LogEntry::rebind_query("SELECT ?, ?, ?", &ints(&["1"])),
+            "SELECT 1, ?/* unbound */, ?/* unbound */"
+        );
+        assert_eq!(
+            LogEntry::rebind_query("SELECT ?", &ints(&["1", "2", "3"])),
+            "SELECT 1 /* unused binds: 2, 3 */"
+        );
+        assert_eq!(
+            LogEntry::rebind_query("SELECT $2, $1, $2, $4", &ints(&["1", "2", "3"])),
+            "SELECT 2, 1, 2, $4/* unbound */ /* unused binds: 3 */"
+        );
+    }
+
+    #[test]
+    fn test_placeholder_styles() -> Result<()> {
+        assert_eq!(
+            LogEntry::replace_query_params("UPDATE t SET a = $2 WHERE b = $1", &ints(&["1", "2"]))?,
+            "UPDATE t SET a = 2 WHERE b = 1"
+        );
+        assert_eq!(
+            LogEntry::replace_query_params(
+                "SELECT * FROM t WHERE a = :id OR b = :id AND c = :name",
+                &ints(&["1", "2"])
+            )?,
+            "SELECT * FROM t WHERE a = 1 OR b = 1 AND c = 2"
+        );
+        assert!(LogEntry::replace_query_params("SELECT $1, $3", &ints(&["1", "2"])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_mismatch_cause() {
+        let cause = |query: &str, binds: &[&str]| {
+            LogEntry {
+                query: query.to_string(),
+                bind_statements: ints(binds),
+                ..LogEntry::default()
+            }
+            .bind_mismatch()
+        };
+
+        assert_eq!(cause("SELECT ?", &["1"]), None);
+        assert_eq!(cause("SELECT ?", &[]), None);
+        assert_eq!(
+            cause("SELECT '?' || ?", &["1", "2"]),
+            Some(MismatchCause::LiteralQuestionMark)
+        );
+        assert_eq!(
+            cause("SELECT ?, ?", &["1"]),
+            Some(MismatchCause::MissingBinds)
+        );
+        assert_eq!(
+            cause("INSERT INTO t VALUES (?, ?)", &["1", "2", "3", "4"]),
+            Some(MismatchCause::BatchExecution)
+        );
+        assert_eq!(
+            cause("SELECT ?, ?", &["1", "2", "3"]),
+            Some(MismatchCause::ExtraBinds)
+        );
+    }
+
+    #[test]
+    fn test_parameter_sets() {
+        let mut entry = LogEntry {
+            query: "INSERT INTO t VALUES (?, ?)".to_string(),
+            bind_statements: ints(&["1", "2", "3", "4", "5", "6"]),
+            ..LogEntry::default()
+        };
+        assert!(entry.parameter_sets().is_none());
+        assert_eq!(entry.bind_mismatch(), Some(MismatchCause::BatchExecution));
+
+        entry.execute_kind = ExecuteKind::ExecuteAll;
+        let sets: Vec<Vec<&str>> = entry
+            .parameter_sets()
+            .unwrap()
+            .iter()
+            .map(|set| set.iter().map(|b| b.value.as_str()).collect())
+            .collect();
+        assert_eq!(sets, vec![vec!["1", "2"], vec!["3", "4"], vec!["5", "6"]]);
+        assert_eq!(entry.bind_mismatch(), None);
+
+        entry.bind_statements.pop();
+        assert!(entry.parameter_sets().is_none());
+        assert_eq!(entry.bind_mismatch(), Some(MismatchCause::ExtraBinds));
+    }
+}
@@ -0,0 +1,429 @@
+use std::io::{BufRead, BufReader};
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context, Result};
+use encoding_rs::Encoding;
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use regex::Regex;
+
+use super::encoding::{detect_encoding, DETECTION_SAMPLE_LEN};
+use super::format::{detect_format, peek, sniff, LogFormat};
+use super::parse_line::LinePatterns;
+use super::{
+    CubridVersion, Decoding, EntryStream, InputEncoding, LogEntries, LogEntry, ParallelLogEntries,
+    SqlLogEntries,
+};
+use crate::input::{decompress, InputSource};
+
+/// A log parser configured once and reused across inputs.
+///
+/// The line patterns are compiled and the worker pool is started when the
+/// parser is built, and both are shared by every input it parses.
+///
+/// ```no_run
+/// # use cubrid_logtopbind_rs::parser::LogParser;
+/// # fn main() -> anyhow::Result<()> {
+/// let parser = LogParser::builder().lenient(true).threads(4).build()?;
+/// for path in ["a.q", "b.q"] {
+///     let file = std::io::BufReader::new(std::fs::File::open(path)?);
+///     for entry in parser.parse(file)? {
+///         println!("{}", entry?.query);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct LogParser {
+    patterns: LinePatterns,
+    lenient: bool,
+    decoding: Decoding,
+    /// Worker pool of the parallel parser, absent with a single thread.
+    pool: Option<Arc<ThreadPool>>,
+    progress: bool,
+}
+
+impl LogParser {
+    pub fn builder() -> LogParserBuilder {
+        LogParserBuilder::default()
+    }
+
+    pub(crate) fn patterns(&self) -> &LinePatterns {
+        &self.patterns
+    }
+
+    /// Parses the `log_top.q` log read from `reader`, yielding entries as
+    /// their blocks complete.
+    pub fn parse<'a, R: BufRead + 'a>(&self, reader: R) -> Result<Box<dyn EntryStream + 'a>> {
+        let (detected, reader) = self.detect_encoding(reader)?;
+        Ok(if let Some(pool) = &self.pool {
+            Box::new(
+                ParallelLogEntries::with_pool(reader, Arc::clone(pool))
+                    .lenient(self.lenient)
+                    .decoding(self.decoding)
+                    .detected_encoding(detected)
+                    .patterns(self.patterns.clone()),
+            )
+        } else {
+            Box::new(
+                LogEntries::new(reader)
+                    .lenient(self.lenient)
+                    .decoding(self.decoding)
+                    .detected_encoding(detected)
+                    .patterns(self.patterns.clone()),
+            )
+        })
+    }
+
+    /// Parses the raw CAS SQL log read from `reader`. `filename` is the name
+    /// of the log file, from which the broker name and CAS number are taken.
+    pub fn parse_sql_log<'a, R: BufRead + 'a>(
+        &self,
+        reader: R,
+        filename: &str,
+    ) -> Result<Box<dyn EntryStream + 'a>> {
+        let (detected, reader) = self.detect_encoding(reader)?;
+        Ok(Box::new(
+            SqlLogEntries::new(reader)
+                .filename(filename)
+                .lenient(self.lenient)
+                .decoding(self.decoding)
+                .detected_encoding(detected)
+                .patterns(self.patterns.clone()),
+        ))
+    }
+
+    /// Detects the encoding of the input from its first
+    /// [`DETECTION_SAMPLE_LEN`] bytes when it is not set, so that the whole
+    /// input, however it is split among threads, is decoded the same way.
+    /// Returns `None` if they are all valid UTF-8, or if the encoding is set.
+    fn detect_encoding<'a, R: BufRead + 'a>(
+        &self,
+        reader: R,
+    ) -> Result<(Option<&'static Encoding>, Box<dyn BufRead + 'a>)> {
+        if self.decoding.encoding != InputEncoding::Auto {
+            return Ok((None, Box::new(reader)));
+        }
+        let (sample, reader) = peek(reader, DETECTION_SAMPLE_LEN)?;
+        Ok((detect_encoding(&sample), reader))
+    }
+
+    /// Parses a whole log held in memory.
+    pub fn parse_str(&self, content: &str) -> Result<Vec<LogEntry>> {
+        self.parse(content.as_bytes())?.collect()
+    }
+
+    /// Opens `input` for parsing, decompressing it if needed. When progress
+    /// is enabled, the bytes read from the input advance `progress_bar`.
+    pub fn open(
+        &self,
+        input: &InputSource,
+        progress_bar: &ProgressBar,
+    ) -> Result<Box<dyn BufRead>> {
+        // Progress is tracked on the raw input so that it stays accurate for
+        // compressed files.
+        decompress(BufReader::new(progress_bar.wrap_read(input.open()?)))
+    }
+
+    /// Opens and parses `input` in `format`, or in the format detected from
+    /// its first [`SNIFF_LEN`](super::SNIFF_LEN) bytes if `format` is `None`.
+    ///
+    /// Returns the entries together with the format they are read in and the
+    /// progress bar tracking them, which is hidden when progress is disabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input cannot be opened or its format cannot be
+    /// detected.
+    pub fn parse_input(
+        &self,
+        input: &InputSource,
+        format: Option<&'static dyn LogFormat>,
+    ) -> Result<(Box<dyn EntryStream>, &'static dyn LogFormat, ProgressBar)> {
+        let progress_bar = self.progress_bar(input.size());
+        let reader = self.open(input, &progress_bar)?;
+        let (format, reader) = match format {
+            Some(format) => (format, reader),
+            None => {
+                let (sample, reader) = sniff(reader)?;
+                let format = detect_format(self, &sample).ok_or_else(|| {
+                    anyhow!(
+                        "Cannot detect the format of {}, pass it with --format",
+                        input.name()
+                    )
+                })?;
+                (format, reader)
+            }
+        };
+        let entries = format.parse(self, reader, &input.file_name())?;
+        Ok((entries, format, progress_bar))
+    }
+
+    /// Creates a progress bar for an input of `total_bytes`, or a spinner if
+    /// the size is unknown. The bar is hidden when progress is disabled.
+    pub fn progress_bar(&self, total_bytes: Option<u64>) -> ProgressBar {
+        if !self.progress {
+            return ProgressBar::hidden();
+        }
+
+        match total_bytes {
+            Some(total_bytes) => {
+                let pb = ProgressBar::new(total_bytes);
+                pb.set_style(
+                    ProgressStyle::default_bar()
+                        .template(
+                            "{spinner:.green} [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({percent}%) - ETA: {eta}",
+                        )
+                        .unwrap()
+                        .progress_chars("#>-"),
+                );
+                pb
+            }
+            None => {
+                // The size of stdin is unknown, so only show the throughput.
+                let pb = ProgressBar::new_spinner();
+                pb.set_style(
+                    ProgressStyle::default_spinner()
+                        .template("{spinner:.green} {bytes} read ({bytes_per_sec})")
+                        .unwrap(),
+                );
+                pb
+            }
+        }
+    }
+}
+
+/// Builder of a [`LogParser`].
+///
+/// Pattern overrides replace the default regex of one kind of line, for
+/// logs tweaked by site-specific tooling. They must keep the capture groups
+/// of the pattern they replace:
+///
+/// - `query_no`: the number as group 1
+/// - `query`: time (1), pid (2), `srv_h_id` (3) and query (4)
+/// - `bind`: no group, the value is the text after the match
+/// - `end`: time (1), pid (2), error code (3), tuple count (4) and elapsed
+///   time (5)
+/// - `filename`: the sql.log name (1) and line number (2)
+#[derive(Debug, Clone)]
+pub struct LogParserBuilder {
+    version: CubridVersion,
+    lenient: bool,
+    decoding: Decoding,
+    threads: usize,
+    progress: bool,
+    log_year: Option<i32>,
+    query_no_pattern: Option<String>,
+    query_pattern: Option<String>,
+    bind_pattern: Option<String>,
+    end_pattern: Option<String>,
+    filename_pattern: Option<String>,
+}
+
+impl Default for LogParserBuilder {
+    fn default() -> Self {
+        Self {
+            version: CubridVersion::default(),
+            lenient: false,
+            decoding: Decoding::default(),
+            threads: 1,
+            progress: false,
+            log_year: None,
+            query_no_pattern: None,
+            query_pattern: None,
+            bind_pattern: None,
+            end_pattern: None,
+            filename_pattern: None,
+        }
+    }
+}
+
+impl LogParserBuilder {
+    /// Restricts the accepted line formats to those of `version`.
+    pub fn version(mut self, version: CubridVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Keeps entries with unparseable bind values instead of failing.
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    pub fn encoding(mut self, encoding: InputEncoding) -> Self {
+        self.decoding.encoding = encoding;
+        self
+    }
+
+    /// Replaces undecodable bytes instead of failing.
+    pub fn lossy(mut self, lossy: bool) -> Self {
+        self.decoding.lossy = lossy;
+        self
+    }
+
+    /// Number of threads used to parse an input (1 parses on the caller's
+    /// thread).
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Shows a progress bar while inputs opened by the parser are read.
+    pub fn progress(mut self, progress: bool) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Year in which the year-less timestamps of CUBRID 9.x are dated.
+    /// Without it their time is left unknown.
+    pub fn log_year(mut self, year: i32) -> Self {
+        self.log_year = Some(year);
+        self
+    }
+
+    pub fn query_no_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.query_no_pattern = Some(pattern.into());
+        self
+    }
+
+    pub fn query_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.query_pattern = Some(pattern.into());
+        self
+    }
+
+    pub fn bind_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.bind_pattern = Some(pattern.into());
+        self
+    }
+
+    pub fn end_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.end_pattern = Some(pattern.into());
+        self
+    }
+
+    pub fn filename_pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.filename_pattern = Some(pattern.into());
+        self
+    }
+
+    /// Compiles the patterns and builds the parser.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a pattern override is not a valid regex or lacks
+    /// the capture groups of the pattern it replaces, or if the worker pool
+    /// cannot be started.
+    pub fn build(self) -> Result<LogParser> {
+        let mut patterns = if self.version == CubridVersion::default() {
+            LinePatterns::default()
+        } else {
+            LinePatterns::new(self.version)
+        };
+
+        patterns.log_year = self.log_year;
+
+        let overrides = [
+            (
+                "query_no",
+                &self.query_no_pattern,
+                1,
+                &mut patterns.re_query_no,
+            ),
+            ("query", &self.query_pattern, 4, &mut patterns.re_query),
+            ("bind", &self.bind_pattern, 0, &mut patterns.re_bind),
+            ("end", &self.end_pattern, 5, &mut patterns.re_end),
+            (
+                "filename",
+                &self.filename_pattern,
+                2,
+                &mut patterns.re_filename,
+            ),
+        ];
+        for (name, pattern, groups, regex) in overrides {
+            if let Some(pattern) = pattern {
+                *regex = compile_override(name, pattern, groups)?;
+            }
+        }
+
+        let pool = if self.threads > 1 {
+            let pool = ThreadPoolBuilder::new().num_threads(self.threads).build()?;
+            Some(Arc::new(pool))
+        } else {
+            None
+        };
+
+        Ok(LogParser {
+            patterns,
+            lenient: self.lenient,
+            decoding: self.decoding,
+            pool,
+            progress: self.progress,
+        })
+    }
+}
+
+fn compile_override(name: &str, pattern: &str, groups: usize) -> Result<Regex> {
+    let regex =
+        Regex::new(pattern).with_context(|| format!("Invalid {} pattern: {}", name, pattern))?;
+    // captures_len() counts the implicit group 0.
+    if regex.captures_len() <= groups {
+        bail!(
+            "The {} pattern needs {} capture groups: {}",
+            name,
+            groups,
+            pattern
+        );
+    }
+    Ok(regex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pattern_overrides() -> Result<()> {
+        // A site that prefixes every block header with its host name.
+        let parser = LogParser::builder()
+            .query_no_pattern(r"^db1 \[Q(\d+)\]-+$")
+            .build()?;
+        let log = "db1 [Q7]-----\n\
+21-02-24 15:30:45.123 (1) execute srv_h_id 1 SELECT ?\n\
+21-02-24 15:30:45.124 (1) bind 1 : INT 3\n";
+
+        let entries = parser.parse_str(log)?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].query_no, "7");
+        assert_eq!(entries[0].bind_statements, vec!["3"]);
+
+        // The same configuration is used by the parallel parser.
+        let parallel = LogParser::builder()
+            .query_no_pattern(r"^db1 \[Q(\d+)\]-+$")
+            .threads(2)
+            .build()?;
+        assert_eq!(parallel.parse_str(log)?[0].query_no, "7");
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_overrides() {
+        assert!(LogParser::builder().query_pattern("(").build().is_err());
+        assert!(LogParser::builder()
+            .end_pattern(r"^(\d+) tuple")
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_reuse_across_inputs() -> Result<()> {
+        let parser = LogParser::builder().lenient(true).build()?;
+        let first = "[Q1]-----\n21-02-24 15:30:45.123 (1) execute srv_h_id 1 SELECT 1\n";
+        let second = "[Q2]-----\n21-02-24 15:30:45.123 (1) bind 1 : RESULTSET 3\n";
+
+        assert_eq!(parser.parse_str(first)?[0].query, "SELECT 1");
+        assert!(parser.parse_str(second)?[0].parse_error.is_some());
+        Ok(())
+    }
+}
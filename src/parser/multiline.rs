@@ -0,0 +1,156 @@
+use super::line_reader::{BufferedLine, LineReader};
+use super::{BindValue, Diagnostic, DiagnosticKind, LogEntry};
+
+/// Whether the lines that follow `bind` and match no line format are
+/// appended to its value. Only strings written without a length are
+/// continued this way: other strings are read by [`PendingValue`], and
+/// values of other types never span lines.
+pub(crate) fn continues_unsized(bind: &BindValue) -> bool {
+    bind.bind_type.is_character_string() && bind.length.is_none()
+}
+
+/// Progress through a string value read up to its declared length.
+///
+/// CUBRID declares the length of a string bind in bytes of the database
+/// charset, counting a terminating NUL, and writes the line breaks of the
+/// value as they are, each counting one byte.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ValueLength {
+    /// Bytes of the value, without the NUL.
+    expected: usize,
+    /// Bytes read so far, counting one for every line break.
+    read: usize,
+}
+
+impl ValueLength {
+    /// Starts counting the value of `bind`, of which `read` bytes are on the
+    /// bind line. Returns `None` when the value has no declared length or is
+    /// complete on its line.
+    pub fn start(bind: &BindValue, read: usize) -> Option<Self> {
+        if !bind.bind_type.is_character_string() {
+            return None;
+        }
+        let expected = bind.length?.checked_sub(1)?;
+        (read < expected).then_some(Self { expected, read })
+    }
+
+    /// Counts the next line, `len` bytes long without its terminator.
+    /// Returns `false`, counting nothing, if it does not fit in the value.
+    pub fn push(&mut self, len: usize) -> bool {
+        if self.read + len + 1 > self.expected {
+            return false;
+        }
+        self.read += len + 1;
+        true
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.read == self.expected
+    }
+}
+
+/// A string bind whose declared length runs past the end of its line.
+///
+/// The lines that follow the bind line are taken into the value until it
+/// reaches its [`ValueLength`], whatever they look like, so that a value
+/// line that resembles a log line is not mistaken for one.
+pub(crate) struct PendingValue {
+    length: ValueLength,
+    lines: Vec<BufferedLine>,
+    /// The bind line, reported if the value turns out to be truncated.
+    bind_line: BufferedLine,
+}
+
+impl PendingValue {
+    /// Starts reading the rest of `bind`, parsed from `text`, the part of the
+    /// bind line `line` after `bind N : `. Returns `None` when the value has
+    /// no declared length or is complete on its line.
+    pub fn start<R>(
+        bind: &mut BindValue,
+        text: &str,
+        line: &str,
+        lines: &LineReader<R>,
+    ) -> Option<Self> {
+        let value = &text[text.find(')')? + 1..];
+        // What precedes the value is ASCII, so it is as long in the input as
+        // in the decoded line.
+        let read = lines.line_len().saturating_sub(line.len() - value.len());
+        let length = ValueLength::start(bind, read)?;
+
+        // The value is kept as is, without the trimming of one-line values.
+        bind.value = value.to_string();
+        Some(Self {
+            length,
+            lines: Vec::new(),
+            bind_line: lines.last_line(line),
+        })
+    }
+
+    /// Adds the next line to the value. Gives the line back if it does not
+    /// fit, in which case the value was cut before it.
+    pub fn push(&mut self, line: BufferedLine) -> Result<(), BufferedLine> {
+        if !self.length.push(line.len) {
+            return Err(line);
+        }
+        self.lines.push(line);
+        Ok(())
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.length.is_complete()
+    }
+
+    /// Appends the lines read to the last bind of `entry`.
+    pub fn finish(self, entry: &mut LogEntry) {
+        append_lines(entry, &self.lines);
+    }
+
+    /// Ends a value cut short by the input, either before `next`, the line
+    /// that did not fit, or at the end of the input.
+    ///
+    /// Where the value really ends is unknown, so it keeps the lines read up
+    /// to the first one for which `is_log_line` holds. That line and the
+    /// following ones are returned, with `next`, to be read again. The bind
+    /// is flagged as truncated and a diagnostic is returned for it.
+    pub fn truncate(
+        mut self,
+        entry: &mut LogEntry,
+        next: Option<BufferedLine>,
+        is_log_line: impl Fn(&str) -> bool,
+    ) -> (Diagnostic, Vec<BufferedLine>) {
+        let end = self
+            .lines
+            .iter()
+            .position(|line| is_log_line(&line.text))
+            .unwrap_or(self.lines.len());
+        let mut unread = self.lines.split_off(end);
+        self.length.read -= unread.iter().map(|line| line.len + 1).sum::<usize>();
+        unread.extend(next);
+
+        append_lines(entry, &self.lines);
+        if let Some(bind) = entry.bind_statements.last_mut() {
+            bind.truncated = true;
+        }
+        let diagnostic = Diagnostic {
+            kind: DiagnosticKind::TruncatedBind,
+            line_no: self.bind_line.line_no,
+            byte_offset: self.bind_line.line_offset,
+            text: self.bind_line.text,
+            query_no: (!entry.query_no.is_empty()).then(|| entry.query_no.clone()),
+            message: format!(
+                "Value truncated to {} of its {} bytes",
+                self.length.read, self.length.expected
+            ),
+        };
+        (diagnostic, unread)
+    }
+}
+
+fn append_lines(entry: &mut LogEntry, lines: &[BufferedLine]) {
+    if let Some(bind) = entry.bind_statements.last_mut() {
+        for line in lines {
+            bind.value.push('\n');
+            bind.value.push_str(&line.text);
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::VecDeque;
+use std::io::BufRead;
+use std::sync::Arc;
+
+use anyhow::Result;
+use encoding_rs::Encoding;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use regex::bytes::Regex;
+
+use super::encoding::detect_encoding;
+use super::multiline::ValueLength;
+use super::parse_line::parse_bind_value;
+use super::parse_line::LinePatterns;
+use super::{
+    CubridVersion, Decoding, Diagnostic, EntryStream, InputEncoding, LogEntries, LogEntry,
+};
+
+/// Number of `[Qn]` blocks handed to a worker at once.
+const BLOCKS_PER_CHUNK: usize = 1024;
+
+/// A run of whole blocks together with its position in the input.
+struct Chunk {
+    /// Raw bytes, decoded by the worker that parses the chunk.
+    bytes: Vec<u8>,
+    lines_before: u64,
+    byte_offset: u64,
+}
+
+/// Parallel variant of [`LogEntries`](super::LogEntries).
+///
+/// The input is split at `[Qn]-----` headers into chunks of whole blocks,
+/// which are parsed on a worker pool. A header inside a string value read up
+/// to its declared length is part of the value and does not split the input.
+/// Only `threads` chunks are in flight at
+/// any time and entries are yielded in their original order.
+pub struct ParallelLogEntries<R> {
+    reader: R,
+    pool: Arc<ThreadPool>,
+    re_query_no: Regex,
+    re_bind: Regex,
+    /// Rest of the last bind value, when it runs past its line.
+    value: Option<ValueLength>,
+    threads: usize,
+    lenient: bool,
+    decoding: Decoding,
+    /// Encoding of the lines that are not valid UTF-8, detected once for the
+    /// whole input so that every chunk is decoded the same way.
+    detected: Option<&'static Encoding>,
+    patterns: LinePatterns,
+    line: Vec<u8>,
+    /// First line of the next block, already read from the input.
+    pending_header: Option<Vec<u8>>,
+    /// Lines and bytes read from the input, excluding `pending_header`.
+    lines_read: u64,
+    bytes_read: u64,
+    parsed: VecDeque<Result<LogEntry>>,
+    diagnostics: Vec<Diagnostic>,
+    finished: bool,
+}
+
+impl<R: BufRead> ParallelLogEntries<R> {
+    pub fn new(reader: R, threads: usize) -> Result<Self> {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads.max(1))
+            .build()?;
+        Ok(Self::with_pool(reader, Arc::new(pool)))
+    }
+
+    /// Parses on `pool`, shared with other inputs, with as many chunks in
+    /// flight as it has threads.
+    pub(crate) fn with_pool(reader: R, pool: Arc<ThreadPool>) -> Self {
+        Self {
+            reader,
+            threads: pool.current_num_threads(),
+            pool,
+            re_query_no: Regex::new(LinePatterns::default().re_query_no.as_str()).unwrap(),
+            re_bind: Regex::new(LinePatterns::default().re_bind.as_str()).unwrap(),
+            value: None,
+            lenient: false,
+            decoding: Decoding::default(),
+            detected: None,
+            patterns: LinePatterns::default(),
+            line: Vec::new(),
+            pending_header: None,
+            lines_read: 0,
+            bytes_read: 0,
+            parsed: VecDeque::new(),
+            diagnostics: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// Keeps entries with unparseable bind values instead of failing.
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Sets how the input bytes are decoded.
+    pub fn decoding(mut self, decoding: Decoding) -> Self {
+        self.decoding = decoding;
+        self
+    }
+
+    /// Sets the encoding detected for the whole input, used for the lines
+    /// that are not valid UTF-8 when the encoding is detected.
+    pub(crate) fn detected_encoding(mut self, encoding: Option<&'static Encoding>) -> Self {
+        self.detected = encoding;
+        self
+    }
+
+    /// Restricts the accepted line formats to those of `version`.
+    pub fn version(self, version: CubridVersion) -> Self {
+        self.patterns(LinePatterns::new(version))
+    }
+
+    pub(crate) fn patterns(mut self, patterns: LinePatterns) -> Self {
+        // Blocks are split on the `[Qn]` header the workers recognize, outside
+        // of the values of the bind lines they recognize.
+        self.re_query_no = Regex::new(patterns.re_query_no.as_str()).unwrap();
+        self.re_bind = Regex::new(patterns.re_bind.as_str()).unwrap();
+        self.patterns = patterns;
+        self
+    }
+
+    /// Reads up to [`BLOCKS_PER_CHUNK`] blocks into a single chunk.
+    /// Returns `None` once the input is exhausted.
+    fn read_chunk(&mut self) -> Result<Option<Chunk>> {
+        let lines_before = self.lines_read;
+        let byte_offset = self.bytes_read;
+        let mut bytes = Vec::new();
+        let mut blocks = 0;
+
+        if let Some(header) = self.pending_header.take() {
+            self.add_line(&mut bytes, &header);
+            blocks += 1;
+        }
+
+        loop {
+            self.line.clear();
+            if self.reader.read_until(b'\n', &mut self.line)? == 0 {
+                break;
+            }
+
+            let trimmed = match self.line.iter().rposition(|&b| b != b'\n' && b != b'\r') {
+                Some(end) => &self.line[..=end],
+                None => &[],
+            };
+            // Counted like the workers do, without the `\n` and one `\r`.
+            let line = self.line.strip_suffix(b"\n").unwrap_or(&self.line);
+            let len = line.strip_suffix(b"\r").unwrap_or(line).len();
+            if let Some(value) = &mut self.value {
+                if value.push(len) {
+                    if value.is_complete() {
+                        self.value = None;
+                    }
+                    let line = std::mem::take(&mut self.line);
+                    self.add_line(&mut bytes, &line);
+                    self.line = line;
+                    continue;
+                }
+                // The value was cut short before this line.
+                self.value = None;
+            }
+
+            if self.re_query_no.is_match(trimmed) {
+                if blocks == BLOCKS_PER_CHUNK {
+                    self.pending_header = Some(std::mem::take(&mut self.line));
+                    break;
+                }
+                blocks += 1;
+            } else if let Some(bind) = self.re_bind.find(trimmed) {
+                self.value = value_length(&trimmed[bind.end()..], len - bind.end());
+            }
+            let line = std::mem::take(&mut self.line);
+            self.add_line(&mut bytes, &line);
+            self.line = line;
+        }
+
+        Ok((!bytes.is_empty()).then_some(Chunk {
+            bytes,
+            lines_before,
+            byte_offset,
+        }))
+    }
+
+    fn add_line(&mut self, bytes: &mut Vec<u8>, line: &[u8]) {
+        bytes.extend_from_slice(line);
+        self.lines_read += 1;
+        self.bytes_read += line.len() as u64;
+    }
+
+    /// Reads the next batch of chunks and parses them on the worker pool.
+    fn fill(&mut self) -> Result<()> {
+        let mut chunks = Vec::with_capacity(self.threads);
+        while chunks.len() < self.threads {
+            match self.read_chunk()? {
+                Some(chunk) => chunks.push(chunk),
+                None => {
+                    self.finished = true;
+                    break;
+                }
+            }
+        }
+
+        // Without an encoding detected beforehand, it is detected from the
+        // first chunk that needs one and kept for the following chunks.
+        if self.decoding.encoding == InputEncoding::Auto && self.detected.is_none() {
+            self.detected = chunks
+                .iter()
+                .find_map(|chunk| detect_encoding(&chunk.bytes));
+        }
+
+        let lenient = self.lenient;
+        let decoding = self.decoding;
+        let detected = self.detected;
+        let patterns = &self.patterns;
+        let results: Vec<(Vec<Result<LogEntry>>, Vec<Diagnostic>)> = self.pool.install(|| {
+            chunks
+                .par_iter()
+                .map(|chunk| {
+                    let mut parser = LogEntries::new(chunk.bytes.as_slice())
+                        .lenient(lenient)
+                        .decoding(decoding)
+                        .detected_encoding(detected)
+                        .patterns(patterns.clone())
+                        .starting_at(chunk.lines_before, chunk.byte_offset);
+                    let entries = parser.by_ref().collect();
+                    (entries, parser.take_diagnostics())
+                })
+                .collect()
+        });
+
+        // A failed chunk ends the run after the entries that precede the error.
+        for (entries, diagnostics) in results {
+            self.diagnostics.extend(diagnostics);
+            for entry in entries {
+                let failed = entry.is_err();
+                self.parsed.push_back(entry);
+                if failed {
+                    self.finished = true;
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Counts the value of the bind whose text, after `bind N : `, is `text`, of
+/// which `len` bytes are in the input. Returns `None` unless the value runs
+/// past its line.
+fn value_length(text: &[u8], len: usize) -> Option<ValueLength> {
+    // The type and length before the `)` are ASCII, unlike the value.
+    let end = text.iter().position(|&b| b == b')')? + 1;
+    let bind = parse_bind_value(std::str::from_utf8(&text[..end]).ok()?).ok()?;
+    ValueLength::start(&bind, len.saturating_sub(end))
+}
+
+impl<R: BufRead> EntryStream for ParallelLogEntries<R> {
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+}
+
+impl<R: BufRead> Iterator for ParallelLogEntries<R> {
+    type Item = Result<LogEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.parsed.is_empty() && !self.finished {
+            if let Err(e) = self.fill() {
+                self.finished = true;
+                return Some(Err(e));
+            }
+        }
+        self.parsed.pop_front()
+    }
+}
@@ -1,101 +1,386 @@
-use regex::Regex;
+use chrono::NaiveDateTime;
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
 
-pub enum ParsedLine<'a> {
-    QueryNo(&'a str),
-    Bind(&'a str),
-    Query(&'a str),
-    End,
-    Filename(&'a str),
-}
-
-pub fn parse_line<'a>(
-    line: &'a str,
-    re_query_no: &Regex,
-    re_bind: &Regex,
-    re_query: &Regex,
-    re_end: &Regex,
-    re_filename: &Regex,
-) -> Option<ParsedLine<'a>> {
-    if let Some(caps) = re_query_no.captures(line) {
-        // Note: using .get(1) is a bit safer than indexing.
-        if let Some(m) = caps.get(1) {
-            return Some(ParsedLine::QueryNo(m.as_str()));
-        }
-    } else if let Some(mat) = re_bind.find(line) {
-        // Everything after the match is the captured text.
-        let captured_text = &line[mat.end()..];
-        return Some(ParsedLine::Bind(captured_text));
-    } else if let Some(caps) = re_query.captures(line) {
-        if let Some(m) = caps.get(1) {
-            return Some(ParsedLine::Query(m.as_str()));
+use super::{CubridVersion, ExecuteKind, QUERY_NO_PATTERN};
+
+lazy_static! {
+    static ref DEFAULT_PATTERNS: LinePatterns = LinePatterns::new(CubridVersion::default());
+}
+
+/// Regexes recognizing the lines of a broker log.
+///
+/// Cloning shares the compiled regexes, so a set of patterns is only compiled
+/// once however many parsers use it.
+#[derive(Clone)]
+pub struct LinePatterns {
+    /// `[Qn]` header, with the number as group 1.
+    pub re_query_no: Regex,
+    /// Start of an execution: time (1), pid (2), `srv_h_id` (3) and query (4).
+    pub re_query: Regex,
+    /// Bind line, whose value is the text after the match.
+    pub re_bind: Regex,
+    /// End of an execution: time (1), pid (2), error code (3), tuple count
+    /// (4) and elapsed time (5).
+    pub re_end: Regex,
+    /// `<sql.log>:<line>` reference, with the file name as group 1 and the
+    /// line as group 2.
+    pub re_filename: Regex,
+    /// Any line starting with a timestamp, with the rest as group 3.
+    pub re_timestamped: Regex,
+    /// Year in which the year-less timestamps of CUBRID 9.x are dated. Their
+    /// time is left unknown without it.
+    pub log_year: Option<i32>,
+}
+
+impl Default for LinePatterns {
+    /// Patterns accepting every supported CUBRID release, compiled once per
+    /// process.
+    fn default() -> Self {
+        DEFAULT_PATTERNS.clone()
+    }
+}
+
+impl LinePatterns {
+    /// Builds the patterns for the log format of `version`.
+    pub fn new(version: CubridVersion) -> Self {
+        // Captures the time as group 1 and the process id as group 2.
+        let timestamp = format!(r"((?:{}))\s\((\d+)\)", version.timestamp_pattern());
+        let execute = version.execute_pattern();
+
+        let re_query_no = Regex::new(QUERY_NO_PATTERN).unwrap();
+        let re_query = Regex::new(&format!(
+            r"^{} (?:{}) srv_h_id (\d*) (.*)$",
+            timestamp, execute
+        ))
+        .unwrap();
+        let re_bind = Regex::new(&format!(r"^{} bind \d+ : ", timestamp)).unwrap();
+
+        let re_end = Regex::new(&format!(
+            r"^{} (?:{}) (?:error:(-\d+)|\d+) tuple (\d+) time (\d+(?:\.\d+)?).*$",
+            timestamp, execute
+        ))
+        .unwrap();
+        let re_filename =
+            Regex::new(r"^([a-zA-Z0-9][a-zA-Z0-9_\.-]{0,150}[a-zA-Z0-9]):(\d{1,6})$").unwrap();
+        let re_timestamped = Regex::new(&format!(r"^{} (.*)$", timestamp)).unwrap();
+
+        Self {
+            re_query_no,
+            re_query,
+            re_bind,
+            re_end,
+            re_filename,
+            re_timestamped,
+            log_year: None,
         }
-    } else if re_end.is_match(line) {
-        return Some(ParsedLine::End);
-    } else if let Some(caps) = re_filename.captures(line) {
-        if let Some(m) = caps.get(1) {
-            return Some(ParsedLine::Filename(m.as_str()));
+    }
+
+    /// Splits a line starting with a timestamp into its header and the rest
+    /// of the line.
+    pub fn parse_timestamped<'a>(&self, line: &'a str) -> Option<(LineHeader, &'a str)> {
+        let caps = self.re_timestamped.captures(line)?;
+        let header = LineHeader::from_captures(&caps, self.log_year)?;
+        Some((header, caps.get(3)?.as_str()))
+    }
+
+    /// Parses `line` with these patterns.
+    pub fn parse<'a>(&self, line: &'a str) -> Option<ParsedLine<'a>> {
+        if let Some(caps) = self.re_query_no.captures(line) {
+            // Note: using .get(1) is a bit safer than indexing.
+            if let Some(m) = caps.get(1) {
+                return Some(ParsedLine::QueryNo(m.as_str()));
+            }
+        } else if let Some(mat) = self.re_bind.find(line) {
+            // Everything after the match is the captured text.
+            let captured_text = &line[mat.end()..];
+            return Some(ParsedLine::Bind(captured_text));
+        } else if let Some(caps) = self.re_query.captures(line) {
+            if let (Some(header), Some(m)) =
+                (LineHeader::from_captures(&caps, self.log_year), caps.get(4))
+            {
+                // The verb is the first word after the `(pid)`.
+                let verb = caps.get(2).and_then(|pid| {
+                    line[pid.end()..]
+                        .trim_start_matches(')')
+                        .split_whitespace()
+                        .next()
+                });
+                return Some(ParsedLine::Query {
+                    header,
+                    kind: verb.map_or(ExecuteKind::Execute, ExecuteKind::from_verb),
+                    srv_h_id: caps.get(3).and_then(|id| id.as_str().parse().ok()),
+                    query: m.as_str(),
+                });
+            }
+        } else if let Some(caps) = self.re_end.captures(line) {
+            if let (Some(header), Some(result)) = (
+                LineHeader::from_captures(&caps, self.log_year),
+                ExecuteResult::from_captures(&caps),
+            ) {
+                return Some(ParsedLine::End(header, result));
+            }
+        } else if let Some(caps) = self.re_filename.captures(line) {
+            if let (Some(m), Some(line_no)) = (caps.get(1), caps.get(2)) {
+                if let Ok(line_no) = line_no.as_str().parse() {
+                    return Some(ParsedLine::Filename(m.as_str(), line_no));
+                }
+            }
         }
+        None
+    }
+}
+
+/// Timestamp and process id that prefix every execute and bind line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineHeader {
+    /// Unknown for a year-less timestamp when no year is given.
+    pub time: Option<NaiveDateTime>,
+    pub pid: u32,
+}
+
+impl LineHeader {
+    /// Builds a header from the time (group 1) and pid (group 2) captures,
+    /// dating year-less timestamps in `log_year`.
+    fn from_captures(caps: &Captures, log_year: Option<i32>) -> Option<Self> {
+        let time = parse_log_time(caps.get(1)?.as_str(), log_year)?;
+        let pid = caps.get(2)?.as_str().parse().ok()?;
+        Some(Self { time, pid })
+    }
+}
+
+/// Parses a `yy-mm-dd hh:mi:ss.mss` or a year-less `mm/dd hh:mi:ss.mss`
+/// timestamp, dating the latter in `log_year`. Returns `None` if the
+/// timestamp is invalid, and `Some(None)` if it is a valid year-less one
+/// whose year is unknown.
+fn parse_log_time(text: &str, log_year: Option<i32>) -> Option<Option<NaiveDateTime>> {
+    if text.contains('/') {
+        let dated = |year: i32| {
+            let dated = format!("{}/{}", year, text);
+            NaiveDateTime::parse_from_str(&dated, "%Y/%m/%d %H:%M:%S%.3f").ok()
+        };
+        // Checked in a leap year, so that February 29 is valid whatever
+        // the year turns out to be.
+        dated(2000)?;
+        Some(log_year.and_then(dated))
+    } else {
+        NaiveDateTime::parse_from_str(text, "%y-%m-%d %H:%M:%S%.3f")
+            .ok()
+            .map(Some)
     }
-    None
+}
+
+/// Outcome reported by the `execute ... tuple N time T` line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExecuteResult {
+    /// CUBRID error code, present only when the execution failed.
+    pub error_code: Option<i32>,
+    pub tuple_count: u64,
+    /// Elapsed time in seconds.
+    pub elapsed: f64,
+}
+
+impl ExecuteResult {
+    /// Builds a result from the error code (group 3), tuple count (group 4)
+    /// and elapsed time (group 5) captures.
+    fn from_captures(caps: &Captures) -> Option<Self> {
+        let error_code = match caps.get(3) {
+            Some(m) => Some(m.as_str().parse().ok()?),
+            None => None,
+        };
+        let tuple_count = caps.get(4)?.as_str().parse().ok()?;
+        let elapsed = caps.get(5)?.as_str().parse().ok()?;
+        Some(Self {
+            error_code,
+            tuple_count,
+            elapsed,
+        })
+    }
+}
+
+pub enum ParsedLine<'a> {
+    QueryNo(&'a str),
+    Bind(&'a str),
+    Query {
+        header: LineHeader,
+        kind: ExecuteKind,
+        srv_h_id: Option<u32>,
+        query: &'a str,
+    },
+    End(LineHeader, ExecuteResult),
+    Filename(&'a str, u32),
+}
+
+lazy_static! {
+    static ref CAS_LOG_NAME: Regex = Regex::new(r"^(.+)_(\d+)\.sql\.log$").unwrap();
+}
+
+/// Splits a standard `<broker>_<cas>.sql.log` file name into the broker name
+/// and the CAS number. Returns `None` for any other naming.
+pub fn split_cas_log_name(filename: &str) -> Option<(&str, u32)> {
+    let caps = CAS_LOG_NAME.captures(filename)?;
+    let broker_name = caps.get(1)?.as_str();
+    let cas_id = caps.get(2)?.as_str().parse().ok()?;
+    Some((broker_name, cas_id))
 }
 
 use anyhow::{bail, Result};
 
-/// Parses a single line and returns its value as a [`String`].
-///
-/// The function handles the following cases:
-/// - `"NULL"`: returns `"NULL"`
-/// - `"SHORT <value>"`: returns the `<value>` (ignoring numeric parsing)
-/// - `"INT <value>"`: returns the `<value>`
-/// - `"VARCHAR (<number>)<value>"`: returns `<value>`, where `<number>` is
+use super::{BindType, BindValue};
+
+/// Parses the value part of a bind line into a [`BindValue`].
 ///
-/// expected to be within parentheses and is ignored.
+/// The broker writes every bind as `<TYPE> <value>`, where the value layout
+/// depends on the type:
+/// - `"NULL"`: a [`BindType::Null`] value
+/// - character strings (`CHAR`, `VARCHAR`, `NCHAR`, `VARNCHAR`, `ENUM`,
+///   `JSON`): `(<length>)<value>`, where `<length>` is kept as the declared length
+/// - bit strings (`BIT`, `VARBIT`): `(<length>)<hex digits>`
+/// - collections (`SET`, `MULTISET`, `SEQUENCE`): `(<count>) {<element>, ...}`,
+///   with the elements written without their type name
+/// - every other type (numbers, dates and times, LOB locators, OIDs): the
+///   `<value>` as is
 ///
 /// # Errors
 ///
-/// Returns an error if the input format is unrecognized or if the VARCHAR format is malformed.
-pub fn parse_bind_value(line: &str) -> Result<String> {
+/// Returns an error if the type is unrecognized or if the value is malformed for its type.
+pub fn parse_bind_value(line: &str) -> Result<BindValue> {
     let trimmed = line.trim();
-    if trimmed == "NULL" {
-        Ok("NULL".to_string())
-    } else if trimmed.starts_with("SHORT ") {
-        let value_str = trimmed
-            .strip_prefix("SHORT ")
-            .expect("Prefix should exist")
-            .trim();
-        Ok(value_str.to_string())
-    } else if trimmed.starts_with("INT ") {
-        let value_str = trimmed
-            .strip_prefix("INT ")
-            .expect("Prefix should exist")
-            .trim();
-        Ok(value_str.to_string())
-    } else if trimmed.starts_with("NUMERIC ") {
-        let value_str = trimmed
-            .strip_prefix("NUMERIC ")
-            .expect("Prefix should exist")
-            .trim();
-        Ok(value_str.to_string())
-    } else if trimmed.starts_with("BIGINT ") {
-        let value_str = trimmed
-            .strip_prefix("BIGINT ")
-            .expect("Prefix should exist")
-            .trim();
-        Ok(value_str.to_string())
-    } else if trimmed.starts_with("VARCHAR") {
+    let name_end = trimmed
+        .find(|c: char| !c.is_ascii_uppercase())
+        .unwrap_or(trimmed.len());
+    let (name, rest) = trimmed.split_at(name_end);
+
+    let Some(bind_type) = BindType::from_name(name) else {
+        bail!("Unrecognized field format");
+    };
+
+    if bind_type == BindType::Null {
+        if !rest.is_empty() {
+            bail!("Unrecognized field format");
+        }
+        return Ok(BindValue::null());
+    }
+
+    if bind_type.is_character_string() || bind_type.is_bit_string() {
         // Expected format: "VARCHAR (<number>)<value>"
         // Find the first occurrence of ')' to separate the length info from the value.
-        if let Some(close_paren_idx) = trimmed.find(')') {
-            let content = trimmed[close_paren_idx + 1..].trim();
-            Ok(content.to_string())
-        } else {
-            eprintln!("Error parsing line: {}", line);
-            bail!("Malformed VARCHAR field: missing ')'");
+        let Some(close_paren_idx) = rest.find(')') else {
+            bail!("Malformed {} field: missing ')'", bind_type);
+        };
+        let length = rest[..close_paren_idx]
+            .trim()
+            .trim_start_matches('(')
+            .parse()
+            .ok();
+        let content = rest[close_paren_idx + 1..].trim();
+        if bind_type.is_bit_string() && !content.bytes().all(|b| b.is_ascii_hexdigit()) {
+            bail!("Malformed {} field: expected hex digits", bind_type);
         }
-    } else {
-        eprintln!("Error parsing line: {}", line);
-        bail!("Unrecognized field format");
+        return Ok(BindValue::new(bind_type, length, content));
+    }
+
+    let Some(value) = rest.strip_prefix(' ').map(str::trim) else {
+        bail!("Malformed {} field: missing value", bind_type);
+    };
+
+    if bind_type.is_collection() {
+        return match parse_collection(value) {
+            Some(elements) => {
+                let body = &value[value.find('{').unwrap_or(0)..];
+                let mut bind = BindValue::new(bind_type, None, body);
+                bind.elements = elements;
+                Ok(bind)
+            }
+            None => {
+                bail!("Malformed {} field: expected (<count>) {{...}}", bind_type);
+            }
+        };
     }
+
+    Ok(BindValue::new(bind_type, None, value))
+}
+
+/// Parses the `(<count>) {<element>, ...}` value of a collection bind.
+fn parse_collection(value: &str) -> Option<Vec<BindValue>> {
+    let (count, rest) = value.strip_prefix('(')?.split_once(')')?;
+    let count: usize = count.trim().parse().ok()?;
+    let body = rest.trim().strip_prefix('{')?.strip_suffix('}')?;
+
+    let mut elements = Vec::with_capacity(count);
+    let mut rest = body.trim_start();
+    while !rest.is_empty() {
+        let (element, remaining) = split_collection_element(rest);
+        elements.push(element);
+        rest = remaining
+            .trim_start()
+            .strip_prefix(',')
+            .unwrap_or(remaining)
+            .trim_start();
+    }
+
+    (elements.len() == count).then_some(elements)
+}
+
+/// Splits the first element off a collection body.
+///
+/// Character string elements keep their `(<length>)` prefix, which is used to
+/// read exactly that many bytes so that `, ` inside the string is not taken
+/// as a separator. Other elements run up to the next `, `.
+fn split_collection_element(text: &str) -> (BindValue, &str) {
+    if let Some((length, rest)) = text.strip_prefix('(').and_then(|t| t.split_once(')')) {
+        if let Ok(length) = length.parse::<usize>() {
+            let end = length.saturating_sub(1);
+            if rest.is_char_boundary(end.min(rest.len()))
+                && rest.len() >= end
+                && (rest.len() == end || rest[end..].starts_with(','))
+            {
+                let element = BindValue::new(BindType::Varchar, Some(length), &rest[..end]);
+                return (element, &rest[end..]);
+            }
+        }
+    }
+
+    let end = text.find(", ").unwrap_or(text.len());
+    let element = text[..end].trim();
+    (
+        BindValue::new(infer_element_type(element), None, element),
+        &text[end..],
+    )
+}
+
+lazy_static! {
+    static ref ELEMENT_TYPES: Vec<(BindType, Regex)> = [
+        (BindType::Int, r"^-?\d{1,9}$"),
+        (BindType::Bigint, r"^-?\d+$"),
+        (BindType::Double, r"^-?\d*\.\d+(?:[eE][-+]?\d+)?$"),
+        (BindType::Date, r"^\d{1,4}-\d{1,2}-\d{1,2}$"),
+        (BindType::Time, r"^\d{1,2}:\d{1,2}:\d{1,2}$"),
+        (
+            BindType::Timestamp,
+            r"^\d{1,4}-\d{1,2}-\d{1,2} \d{1,2}:\d{1,2}:\d{1,2}$"
+        ),
+        (
+            BindType::Datetime,
+            r"^\d{1,4}-\d{1,2}-\d{1,2} \d{1,2}:\d{1,2}:\d{1,2}\.\d{1,3}$"
+        ),
+    ]
+    .into_iter()
+    .map(|(bind_type, pattern)| (bind_type, Regex::new(pattern).unwrap()))
+    .collect();
+}
+
+/// Collection elements are logged without a type name, so the type is
+/// inferred from the shape of the value.
+fn infer_element_type(element: &str) -> BindType {
+    if element == "NULL" {
+        return BindType::Null;
+    }
+    ELEMENT_TYPES
+        .iter()
+        .find(|(_, re)| re.is_match(element))
+        .map(|(bind_type, _)| *bind_type)
+        .unwrap_or(BindType::Varchar)
 }
 
 #[cfg(test)]
@@ -138,4 +423,165 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn test_parse_bind_type() -> Result<()> {
+        let test_cases = vec![
+            ("NULL", BindType::Null, None),
+            ("SHORT 0", BindType::Short, None),
+            ("INT 42", BindType::Int, None),
+            ("BIGINT 9007199254740993", BindType::Bigint, None),
+            ("NUMERIC 3.14", BindType::Numeric, None),
+            ("VARCHAR (3)42", BindType::Varchar, Some(3)),
+            ("VARCHAR () John Doe", BindType::Varchar, None),
+        ];
+
+        for (input, bind_type, length) in test_cases {
+            let result = parse_bind_value(input)?;
+            assert_eq!(result.bind_type, bind_type, "type of '{}'", input);
+            assert_eq!(result.length, length, "length of '{}'", input);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_all_bind_types() -> Result<()> {
+        // Each tuple consists of (input line, expected type, declared length, value).
+        let test_cases = vec![
+            ("CHAR (4)abc", BindType::Char, Some(4), "abc"),
+            ("NCHAR (7)한글", BindType::Nchar, Some(7), "한글"),
+            ("VARNCHAR (4)xyz", BindType::Varnchar, Some(4), "xyz"),
+            ("BIT (1)ff", BindType::Bit, Some(1), "ff"),
+            ("VARBIT (3)0a1b2c", BindType::Varbit, Some(3), "0a1b2c"),
+            ("MONETARY 12.500000", BindType::Monetary, None, "12.500000"),
+            ("FLOAT 1.500000", BindType::Float, None, "1.500000"),
+            ("DOUBLE -3.250000", BindType::Double, None, "-3.250000"),
+            ("DATE 2024-2-29", BindType::Date, None, "2024-2-29"),
+            ("TIME 13:5:9", BindType::Time, None, "13:5:9"),
+            (
+                "TIMESTAMP 2024-2-29 13:5:9",
+                BindType::Timestamp,
+                None,
+                "2024-2-29 13:5:9",
+            ),
+            (
+                "DATETIME 2024-2-29 13:5:9.123",
+                BindType::Datetime,
+                None,
+                "2024-2-29 13:5:9.123",
+            ),
+            (
+                "TIMESTAMPTZ 2024-2-29 13:5:9 Asia/Seoul",
+                BindType::TimestampTz,
+                None,
+                "2024-2-29 13:5:9 Asia/Seoul",
+            ),
+            (
+                "TIMESTAMPLTZ 2024-2-29 13:5:9 +09:00",
+                BindType::TimestampLtz,
+                None,
+                "2024-2-29 13:5:9 +09:00",
+            ),
+            (
+                "DATETIMETZ 2024-2-29 13:5:9.1 Asia/Seoul KST",
+                BindType::DatetimeTz,
+                None,
+                "2024-2-29 13:5:9.1 Asia/Seoul KST",
+            ),
+            (
+                "DATETIMELTZ 2024-2-29 13:5:9.1 +09:00",
+                BindType::DatetimeLtz,
+                None,
+                "2024-2-29 13:5:9.1 +09:00",
+            ),
+            (
+                "BLOB file:/home/cubrid/lob/ces_123/t.00001_4417",
+                BindType::Blob,
+                None,
+                "file:/home/cubrid/lob/ces_123/t.00001_4417",
+            ),
+            (
+                "CLOB file:/home/cubrid/lob/ces_456/t.00002_1234",
+                BindType::Clob,
+                None,
+                "file:/home/cubrid/lob/ces_456/t.00002_1234",
+            ),
+            ("ENUM (4)red", BindType::Enum, Some(4), "red"),
+            (
+                "JSON (14){\"a\": [1, 2]}",
+                BindType::Json,
+                Some(14),
+                "{\"a\": [1, 2]}",
+            ),
+            ("OBJECT 0|1|2", BindType::Object, None, "0|1|2"),
+            ("USHORT 65535", BindType::Ushort, None, "65535"),
+            ("UINT 4294967295", BindType::Uint, None, "4294967295"),
+            (
+                "UBIGINT 18446744073709551615",
+                BindType::Ubigint,
+                None,
+                "18446744073709551615",
+            ),
+        ];
+
+        for (input, bind_type, length, value) in test_cases {
+            let result = parse_bind_value(input)?;
+            assert_eq!(result.bind_type, bind_type, "type of '{}'", input);
+            assert_eq!(result.length, length, "length of '{}'", input);
+            assert_eq!(result.value, value, "value of '{}'", input);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_collection_binds() -> Result<()> {
+        let set = parse_bind_value("SET (3) {1, 2, 3}")?;
+        assert_eq!(set.bind_type, BindType::Set);
+        assert_eq!(set.value, "{1, 2, 3}");
+        assert_eq!(set.elements, vec!["1", "2", "3"]);
+        assert!(set.elements.iter().all(|e| e.bind_type == BindType::Int));
+
+        let multiset = parse_bind_value("MULTISET (2) {(5)a, b, (2)c}")?;
+        assert_eq!(multiset.bind_type, BindType::Multiset);
+        assert_eq!(multiset.elements, vec!["a, b", "c"]);
+        assert_eq!(multiset.elements[0].bind_type, BindType::Varchar);
+
+        let sequence = parse_bind_value("SEQUENCE (3) {2024-2-29, 1.5, NULL}")?;
+        assert_eq!(sequence.bind_type, BindType::Sequence);
+        let types: Vec<BindType> = sequence.elements.iter().map(|e| e.bind_type).collect();
+        assert_eq!(
+            types,
+            vec![BindType::Date, BindType::Double, BindType::Null]
+        );
+
+        let empty = parse_bind_value("SET (0) {}")?;
+        assert!(empty.elements.is_empty());
+
+        assert!(parse_bind_value("SET (2) {1}").is_err());
+        assert!(parse_bind_value("SEQUENCE 1, 2").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_malformed_binds() {
+        assert!(parse_bind_value("VARCHAR 12").is_err());
+        assert!(parse_bind_value("BIT (1)zz").is_err());
+        assert!(parse_bind_value("INTEGER 1").is_err());
+        assert!(parse_bind_value("DATE").is_err());
+        assert!(parse_bind_value("RESULTSET 1").is_err());
+    }
+
+    #[test]
+    fn test_split_cas_log_name() {
+        assert_eq!(
+            split_cas_log_name("query_editor_3.sql.log"),
+            Some(("query_editor", 3))
+        );
+        assert_eq!(
+            split_cas_log_name("broker1_12.sql.log"),
+            Some(("broker1", 12))
+        );
+        assert_eq!(split_cas_log_name("broker1.sql.log"), None);
+        assert_eq!(split_cas_log_name("example.rs"), None);
+    }
 }
@@ -0,0 +1,251 @@
+/// How the placeholders of a query refer to its binds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaceholderStyle {
+    /// `?`, bound in order.
+    Question,
+    /// `$1`, `$2`, ..., bound by number, in any order and possibly repeated.
+    Numbered,
+    /// `:name`, bound in the order the names first appear, a repeated name
+    /// taking the same bind.
+    Named,
+}
+
+/// A bind placeholder found in a query, as a byte range of the query text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placeholder {
+    pub start: usize,
+    pub end: usize,
+    pub style: PlaceholderStyle,
+    /// Index in the binds of the value the placeholder takes.
+    pub index: usize,
+}
+
+/// Finds the placeholders of `query`, in order.
+///
+/// A query is expected to use one style of placeholder, chosen in this
+/// order: `?` if it has any, otherwise `$n`, otherwise `:name`. See
+/// [`PlaceholderStyle`].
+///
+/// The query is tokenized so that a placeholder inside a string literal
+/// (`'...'`, with `''` escaping a quote), a quoted identifier (`"..."` or
+/// `` `...` ``) or a comment (`-- ...`, `// ...` or `/* ... */`) is not taken
+/// for one. A literal or comment left open runs to the end of the query.
+pub fn find_placeholders(query: &str) -> Vec<Placeholder> {
+    let bytes = query.as_bytes();
+    let mut questions = Vec::new();
+    let mut numbered = Vec::new();
+    let mut named = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        i = match bytes[i] {
+            b'?' => {
+                questions.push(i..i + 1);
+                i + 1
+            }
+            b'$' | b':' if follows_word(bytes, i) => i + 1,
+            b'$' => {
+                let end = word_end(bytes, i + 1, u8::is_ascii_digit);
+                match query[i + 1..end].parse::<usize>() {
+                    Ok(n) if n > 0 && !continues_word(bytes, end) => {
+                        numbered.push((i..end, n - 1));
+                        end
+                    }
+                    _ => end,
+                }
+            }
+            b':' if bytes.get(i + 1).is_some_and(|&b| is_name_start(b)) => {
+                let end = word_end(bytes, i + 1, |&b| is_name_char(b));
+                named.push(i..end);
+                end
+            }
+            quote @ (b'\'' | b'"' | b'`') => skip_quoted(bytes, i + 1, quote),
+            b'-' if bytes.get(i + 1) == Some(&b'-') => skip_line(bytes, i + 2),
+            b'/' if bytes.get(i + 1) == Some(&b'/') => skip_line(bytes, i + 2),
+            b'/' if bytes.get(i + 1) == Some(&b'*') => skip_block_comment(bytes, i + 2),
+            _ => i + 1,
+        };
+    }
+
+    if !questions.is_empty() {
+        questions
+            .into_iter()
+            .enumerate()
+            .map(|(index, range)| Placeholder {
+                start: range.start,
+                end: range.end,
+                style: PlaceholderStyle::Question,
+                index,
+            })
+            .collect()
+    } else if !numbered.is_empty() {
+        numbered
+            .into_iter()
+            .map(|(range, index)| Placeholder {
+                start: range.start,
+                end: range.end,
+                style: PlaceholderStyle::Numbered,
+                index,
+            })
+            .collect()
+    } else {
+        let mut names: Vec<&str> = Vec::new();
+        named
+            .into_iter()
+            .map(|range| {
+                let name = &query[range.start + 1..range.end];
+                let index = match names.iter().position(|n| n.eq_ignore_ascii_case(name)) {
+                    Some(index) => index,
+                    None => {
+                        names.push(name);
+                        names.len() - 1
+                    }
+                };
+                Placeholder {
+                    start: range.start,
+                    end: range.end,
+                    style: PlaceholderStyle::Named,
+                    index,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Number of placeholders in `query`.
+pub fn count_placeholders(query: &str) -> usize {
+    find_placeholders(query).len()
+}
+
+/// Number of binds taken by `placeholders`: one per `?`, the highest `$n`,
+/// or one per distinct `:name`.
+pub fn count_parameters(placeholders: &[Placeholder]) -> usize {
+    placeholders.iter().map(|p| p.index + 1).max().unwrap_or(0)
+}
+
+fn is_name_start(b: u8) -> bool {
+    b.is_ascii_alphabetic() || b == b'_'
+}
+
+fn is_name_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+/// Whether the byte at `i` is part of a word, as the `$` of `a$1` or the
+/// second `:` of `a::b`, rather than the start of a placeholder.
+fn follows_word(bytes: &[u8], i: usize) -> bool {
+    i > 0 && (is_name_char(bytes[i - 1]) || matches!(bytes[i - 1], b'$' | b':'))
+}
+
+fn continues_word(bytes: &[u8], i: usize) -> bool {
+    bytes.get(i).is_some_and(|&b| is_name_char(b) || b == b'$')
+}
+
+fn word_end(bytes: &[u8], i: usize, is_word: impl Fn(&u8) -> bool) -> usize {
+    i + bytes[i..].iter().take_while(|b| is_word(b)).count()
+}
+
+/// Returns the index after the closing `quote`, where a doubled quote stands
+/// for the quote itself.
+fn skip_quoted(bytes: &[u8], mut i: usize, quote: u8) -> usize {
+    while i < bytes.len() {
+        if bytes[i] == quote {
+            if bytes.get(i + 1) != Some(&quote) {
+                return i + 1;
+            }
+            i += 1;
+        }
+        i += 1;
+    }
+    bytes.len()
+}
+
+fn skip_line(bytes: &[u8], i: usize) -> usize {
+    bytes[i..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(bytes.len(), |end| i + end + 1)
+}
+
+fn skip_block_comment(bytes: &[u8], i: usize) -> usize {
+    bytes[i..]
+        .windows(2)
+        .position(|w| w == b"*/")
+        .map_or(bytes.len(), |end| i + end + 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn starts(query: &str) -> Vec<usize> {
+        find_placeholders(query).iter().map(|p| p.start).collect()
+    }
+
+    #[test]
+    fn test_find_placeholders() {
+        assert_eq!(
+            starts("SELECT * FROM t WHERE a = ? AND b = ?"),
+            vec![26, 36]
+        );
+        assert_eq!(starts("SELECT 1"), Vec::<usize>::new());
+        assert_eq!(starts("INSERT INTO t VALUES (?,?)"), vec![22, 24]);
+    }
+
+    #[test]
+    fn test_skip_literals_and_comments() {
+        assert_eq!(count_placeholders("SELECT '?' FROM t WHERE a = ?"), 1);
+        assert_eq!(count_placeholders("SELECT 'it''s ?' , ?"), 1);
+        assert_eq!(
+            count_placeholders(r#"SELECT "what?" FROM t WHERE a = ?"#),
+            1
+        );
+        assert_eq!(count_placeholders("SELECT `a?b` FROM t WHERE a = ?"), 1);
+        assert_eq!(
+            count_placeholders("SELECT a -- why?\nFROM t WHERE a = ?"),
+            1
+        );
+        assert_eq!(
+            count_placeholders("SELECT a // why?\nFROM t WHERE a = ?"),
+            1
+        );
+        assert_eq!(count_placeholders("SELECT /* ? */ a FROM t WHERE a = ?"), 1);
+        assert_eq!(count_placeholders("SELECT a - ? FROM t WHERE a = ? / ?"), 3);
+    }
+
+    #[test]
+    fn test_numbered() {
+        let placeholders = find_placeholders("SELECT $2, $1 FROM t WHERE a = $2 AND b = '$3'");
+        let indices: Vec<usize> = placeholders.iter().map(|p| p.index).collect();
+        assert_eq!(indices, vec![1, 0, 1]);
+        assert_eq!(placeholders[0].style, PlaceholderStyle::Numbered);
+        assert_eq!(count_parameters(&placeholders), 2);
+        assert_eq!(count_placeholders("SELECT a$1, $0, $1a FROM t"), 0);
+    }
+
+    #[test]
+    fn test_named() {
+        let placeholders =
+            find_placeholders("UPDATE t SET a = :a, b = :b WHERE a = :A AND c = '10:30'");
+        let indices: Vec<usize> = placeholders.iter().map(|p| p.index).collect();
+        assert_eq!(indices, vec![0, 1, 0]);
+        assert_eq!(placeholders[0].style, PlaceholderStyle::Named);
+        assert_eq!(count_parameters(&placeholders), 2);
+        assert_eq!(count_placeholders("SELECT a::int, :1 FROM t"), 0);
+    }
+
+    #[test]
+    fn test_style_per_query() {
+        let placeholders = find_placeholders("SELECT :a, $1, ?");
+        assert_eq!(placeholders.len(), 1);
+        assert_eq!(placeholders[0].style, PlaceholderStyle::Question);
+        assert_eq!(starts("SELECT :a, $1"), vec![11]);
+    }
+
+    #[test]
+    fn test_unterminated() {
+        assert_eq!(count_placeholders("SELECT ? WHERE a = 'open ?"), 1);
+        assert_eq!(count_placeholders("SELECT ? /* open ?"), 1);
+        assert_eq!(count_placeholders("SELECT ? -- ?"), 1);
+    }
+}
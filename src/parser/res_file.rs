@@ -0,0 +1,152 @@
+use std::io::BufRead;
+
+use anyhow::Result;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::line_reader::LineReader;
+use super::{Decoding, Diagnostic, DiagnosticKind};
+
+lazy_static! {
+    /// `[Qn]  <max> <min> <avg> <count> (<errors>)`
+    static ref STATS_LINE: Regex = Regex::new(
+        r"^\[Q(\d+)\]\s+(\d+(?:\.\d+)?)\s+(\d+(?:\.\d+)?)\s+(\d+(?:\.\d+)?)\s+(\d+)\s*\(\s*(\d+)\s*\)\s*$"
+    )
+    .unwrap();
+}
+
+/// Execution statistics of one `[Qn]` query, as summarized by
+/// `broker_log_top` in `log_top.res`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryStats {
+    /// Number of the query, matching the `query_no` of its [`LogEntry`](super::LogEntry).
+    pub query_no: String,
+    pub max_time: f64,
+    pub min_time: f64,
+    pub avg_time: f64,
+    pub exec_count: u64,
+    pub error_count: u64,
+}
+
+/// Parses one statistics line. Returns `None` for any other line.
+pub fn parse_stats_line(line: &str) -> Option<QueryStats> {
+    let caps = STATS_LINE.captures(line)?;
+    Some(QueryStats {
+        query_no: caps[1].to_string(),
+        max_time: caps[2].parse().ok()?,
+        min_time: caps[3].parse().ok()?,
+        avg_time: caps[4].parse().ok()?,
+        exec_count: caps[5].parse().ok()?,
+        error_count: caps[6].parse().ok()?,
+    })
+}
+
+/// Streaming parser over a `log_top.res` summary.
+///
+/// The column header and separator lines are skipped. A `[Qn]` line that
+/// cannot be parsed is reported as a [`Diagnostic`].
+pub struct ResEntries<R> {
+    lines: LineReader<R>,
+    line: String,
+    diagnostics: Vec<Diagnostic>,
+    finished: bool,
+}
+
+impl<R: BufRead> ResEntries<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: LineReader::new(reader),
+            line: String::new(),
+            diagnostics: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// Sets how the input bytes are decoded.
+    pub fn decoding(mut self, decoding: Decoding) -> Self {
+        self.lines = self.lines.decoding(decoding);
+        self
+    }
+
+    /// Returns the diagnostics collected so far and clears them.
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+}
+
+impl<R: BufRead> Iterator for ResEntries<R> {
+    type Item = Result<QueryStats>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        loop {
+            match self.lines.read_line(&mut self.line, &mut self.diagnostics) {
+                Ok(true) => {
+                    if let Some(stats) = parse_stats_line(&self.line) {
+                        return Some(Ok(stats));
+                    }
+                    if self.line.starts_with("[Q") {
+                        self.diagnostics.push(Diagnostic {
+                            kind: DiagnosticKind::UnrecognizedLine,
+                            line_no: self.lines.line_no(),
+                            byte_offset: self.lines.line_offset(),
+                            text: self.line.clone(),
+                            query_no: None,
+                            message: "Malformed query statistics".to_string(),
+                        });
+                    }
+                }
+                Ok(false) => {
+                    self.finished = true;
+                    return None;
+                }
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RES: &str = "\
+              max       min       avg   cnt(err)
+-----------------------------------------------------
+[Q1]        1.234     0.010     0.400      12 (0)
+[Q2]        0.002     0.001     0.001       3 (1)
+[Q3]        oops
+";
+
+    #[test]
+    fn test_parse_res_file() -> Result<()> {
+        let mut parser = ResEntries::new(RES.as_bytes());
+        let stats = parser.by_ref().collect::<Result<Vec<_>>>()?;
+
+        assert_eq!(stats.len(), 2);
+        assert_eq!(
+            stats[0],
+            QueryStats {
+                query_no: "1".to_string(),
+                max_time: 1.234,
+                min_time: 0.010,
+                avg_time: 0.400,
+                exec_count: 12,
+                error_count: 0,
+            }
+        );
+        assert_eq!(stats[1].query_no, "2");
+        assert_eq!(stats[1].error_count, 1);
+
+        let diagnostics = parser.take_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line_no, 5);
+        Ok(())
+    }
+}
@@ -0,0 +1,499 @@
+use std::io::BufRead;
+
+use anyhow::Result;
+use encoding_rs::Encoding;
+
+use super::line_reader::{BufferedLine, LineReader};
+use super::multiline::{continues_unsized, PendingValue};
+use super::parse_line::{split_cas_log_name, LineHeader, LinePatterns, ParsedLine};
+use super::{
+    new_diagnostic, push_bind, CubridVersion, Decoding, Diagnostic, DiagnosticKind, EntryStream,
+    LogEntry, Transaction, TransactionOutcome,
+};
+
+/// What a line without a timestamp continues.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Continuation {
+    None,
+    Query,
+    Bind,
+    /// A line that is not used, such as a multi-line `prepare`.
+    Skipped,
+}
+
+/// Streaming parser over a raw CAS SQL log (`sql_log/<broker>_<cas>.sql.log`).
+///
+/// Every `execute` in the log becomes a [`LogEntry`] numbered in order of
+/// appearance, so `query_no` is the execution number within the file and
+/// `source_line` the line of its `execute` line. Connection, prepare, fetch,
+/// and close lines carry no execution and are skipped.
+///
+/// Executions are also grouped into [`Transaction`]s ended by `end_tran
+/// COMMIT|ABORT` or, in auto-commit mode, `auto_commit`/`auto_rollback` lines.
+/// Each entry records its transaction in [`LogEntry::transaction_no`], and
+/// completed transactions are returned by
+/// [`take_transactions`](EntryStream::take_transactions).
+///
+/// Bind values, leniency and diagnostics are handled as in
+/// [`LogEntries`](super::LogEntries).
+pub struct SqlLogEntries<R> {
+    lines: LineReader<R>,
+    patterns: LinePatterns,
+    line: String,
+    filename: String,
+    current: LogEntry,
+    continuation: Continuation,
+    /// Rest of the last bind value, when it runs past its line.
+    pending: Option<PendingValue>,
+    executions: u64,
+    /// Transaction of the executions seen since the last `end_tran`.
+    transaction: Option<Transaction>,
+    transaction_count: u64,
+    transactions: Vec<Transaction>,
+    lenient: bool,
+    diagnostics: Vec<Diagnostic>,
+    finished: bool,
+}
+
+impl<R: BufRead> SqlLogEntries<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: LineReader::new(reader),
+            patterns: LinePatterns::default(),
+            line: String::new(),
+            filename: String::new(),
+            current: LogEntry::default(),
+            continuation: Continuation::None,
+            pending: None,
+            executions: 0,
+            transaction: None,
+            transaction_count: 0,
+            transactions: Vec::new(),
+            lenient: false,
+            diagnostics: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// Sets the name of the SQL log file, recorded as the `filename` of every
+    /// entry. The broker name and CAS number are taken from it when it follows
+    /// the `<broker>_<cas>.sql.log` naming.
+    pub fn filename(mut self, filename: impl Into<String>) -> Self {
+        self.filename = filename.into();
+        self
+    }
+
+    /// Keeps entries with unparseable bind values instead of failing.
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Sets how the input bytes are decoded.
+    pub fn decoding(mut self, decoding: Decoding) -> Self {
+        self.lines = self.lines.decoding(decoding);
+        self
+    }
+
+    /// Sets the encoding detected for the whole input, used for the lines
+    /// that are not valid UTF-8 when the encoding is detected.
+    pub(crate) fn detected_encoding(mut self, encoding: Option<&'static Encoding>) -> Self {
+        self.lines = self.lines.detected_encoding(encoding);
+        self
+    }
+
+    /// Restricts the accepted line formats to those of `version`.
+    pub fn version(mut self, version: CubridVersion) -> Self {
+        self.patterns = LinePatterns::new(version);
+        self
+    }
+
+    pub(crate) fn patterns(mut self, patterns: LinePatterns) -> Self {
+        self.patterns = patterns;
+        self
+    }
+
+    fn in_execution(&self) -> bool {
+        !self.current.query_no.is_empty()
+    }
+
+    /// Adds an execution starting at `header` to the open transaction,
+    /// opening one if needed, and returns the transaction number.
+    fn join_transaction(&mut self, header: LineHeader) -> u64 {
+        let transaction = self.transaction.get_or_insert_with(|| {
+            self.transaction_count += 1;
+            let cas = split_cas_log_name(&self.filename);
+            Transaction {
+                transaction_no: self.transaction_count,
+                broker_name: cas.map(|(broker_name, _)| broker_name.to_string()),
+                cas_id: cas.map(|(_, cas_id)| cas_id),
+                start_time: header.time,
+                ..Transaction::default()
+            }
+        });
+        transaction.execution_count += 1;
+        transaction.transaction_no
+    }
+
+    /// Closes the open transaction, if any.
+    fn end_transaction(&mut self, header: LineHeader, outcome: TransactionOutcome) {
+        if let Some(mut transaction) = self.transaction.take() {
+            transaction.end_time = header.time;
+            transaction.outcome = Some(outcome);
+            self.transactions.push(transaction);
+        }
+    }
+
+    /// Feeds one line into the current execution. Returns the execution when
+    /// the line completes it.
+    fn process_line(&mut self) -> Result<Option<LogEntry>> {
+        let line = std::mem::take(&mut self.line);
+        let result = if self.pending.is_some() {
+            self.continue_value(&line);
+            Ok(None)
+        } else {
+            self.process_parsed_line(&line)
+        };
+        self.line = line;
+        result
+    }
+
+    /// Feeds a line to the value of the last bind, which runs past its line.
+    fn continue_value(&mut self, line: &str) {
+        let Some(pending) = &mut self.pending else {
+            return;
+        };
+        match pending.push(self.lines.last_line(line)) {
+            Ok(()) if pending.is_complete() => {
+                if let Some(pending) = self.pending.take() {
+                    pending.finish(&mut self.current);
+                }
+            }
+            Ok(()) => {}
+            Err(next) => self.truncate_value(Some(next)),
+        }
+    }
+
+    /// Ends the pending bind value, cut short before `next` or by the end of
+    /// the input, and hands back the lines that do not belong to it.
+    fn truncate_value(&mut self, next: Option<BufferedLine>) {
+        if let Some(pending) = self.pending.take() {
+            let patterns = &self.patterns;
+            let (diagnostic, unread) = pending.truncate(&mut self.current, next, |line| {
+                patterns.parse_timestamped(line).is_some()
+            });
+            self.diagnostics.push(diagnostic);
+            self.lines.unread(unread);
+        }
+    }
+
+    fn process_parsed_line(&mut self, line: &str) -> Result<Option<LogEntry>> {
+        let mut completed = None;
+
+        match self.patterns.parse(line) {
+            Some(ParsedLine::Query {
+                header,
+                kind,
+                srv_h_id,
+                query,
+            }) => {
+                // An execution without an end line is kept as it is.
+                if self.in_execution() {
+                    completed = Some(std::mem::take(&mut self.current));
+                }
+                self.executions += 1;
+                let transaction_no = self.join_transaction(header);
+                self.current = LogEntry {
+                    query_no: self.executions.to_string(),
+                    filename: self.filename.clone(),
+                    query: query.to_string(),
+                    execute_kind: kind,
+                    start_time: header.time,
+                    pid: Some(header.pid),
+                    srv_h_id,
+                    source_line: u32::try_from(self.lines.line_no()).ok(),
+                    transaction_no: Some(transaction_no),
+                    ..LogEntry::default()
+                };
+                if let Some((broker_name, cas_id)) = split_cas_log_name(&self.filename) {
+                    self.current.broker_name = Some(broker_name.to_string());
+                    self.current.cas_id = Some(cas_id);
+                }
+                self.continuation = Continuation::Query;
+            }
+            Some(ParsedLine::Bind(text)) if self.in_execution() => {
+                push_bind(
+                    &mut self.current,
+                    text,
+                    self.lenient,
+                    &self.lines,
+                    &mut self.diagnostics,
+                )?;
+                self.continuation = Continuation::None;
+                if let Some(bind) = self.current.bind_statements.last_mut() {
+                    self.pending = PendingValue::start(bind, text, line, &self.lines);
+                    if continues_unsized(bind) {
+                        self.continuation = Continuation::Bind;
+                    }
+                }
+            }
+            Some(ParsedLine::End(header, result)) if self.in_execution() => {
+                self.current.end_time = header.time;
+                self.current.error_code = result.error_code;
+                self.current.tuple_count = Some(result.tuple_count);
+                self.current.elapsed = Some(result.elapsed);
+                if let Some(transaction) = &mut self.transaction {
+                    transaction.end_time = header.time;
+                }
+                completed = Some(std::mem::take(&mut self.current));
+                self.continuation = Continuation::None;
+            }
+            _ if line.is_empty() => {
+                // Ignore empty lines.
+            }
+            _ => {
+                if let Some((header, rest)) = self.patterns.parse_timestamped(line) {
+                    match rest {
+                        "end_tran COMMIT" | "auto_commit" => {
+                            self.end_transaction(header, TransactionOutcome::Commit)
+                        }
+                        "end_tran ABORT" | "auto_rollback" => {
+                            self.end_transaction(header, TransactionOutcome::Abort)
+                        }
+                        // prepare, fetch, close_req, connect and the like.
+                        _ => {}
+                    }
+                    self.continuation = Continuation::Skipped;
+                } else {
+                    self.continue_line(line);
+                }
+            }
+        }
+
+        Ok(completed)
+    }
+
+    /// Appends a line without a timestamp to what it continues.
+    fn continue_line(&mut self, line: &str) {
+        match self.continuation {
+            Continuation::Query => {
+                self.current.query.push('\n');
+                self.current.query.push_str(line);
+            }
+            Continuation::Bind => {
+                if let Some(last) = self.current.bind_statements.last_mut() {
+                    last.value.push('\n');
+                    last.value.push_str(line);
+                }
+            }
+            Continuation::Skipped => {}
+            Continuation::None => {
+                self.diagnostics.push(new_diagnostic(
+                    &self.lines,
+                    &self.current,
+                    DiagnosticKind::UnrecognizedLine,
+                    line,
+                    "Unrecognized line".to_string(),
+                ));
+            }
+        }
+    }
+}
+
+impl<R: BufRead> EntryStream for SqlLogEntries<R> {
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    fn take_transactions(&mut self) -> Vec<Transaction> {
+        std::mem::take(&mut self.transactions)
+    }
+}
+
+impl<R: BufRead> Iterator for SqlLogEntries<R> {
+    type Item = Result<LogEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        loop {
+            match self.lines.read_line(&mut self.line, &mut self.diagnostics) {
+                Ok(true) => match self.process_line() {
+                    Ok(Some(entry)) => return Some(Ok(entry)),
+                    Ok(None) => {}
+                    Err(e) => {
+                        self.finished = true;
+                        return Some(Err(e));
+                    }
+                },
+                Ok(false) if self.pending.is_some() => self.truncate_value(None),
+                Ok(false) => {
+                    self.finished = true;
+                    // The log stopped before the transaction ended.
+                    self.transactions.extend(self.transaction.take());
+                    if !self.in_execution() {
+                        return None;
+                    }
+                    return Some(Ok(std::mem::take(&mut self.current)));
+                }
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::BindType;
+
+    const SQL_LOG: &str = r#"21-02-24 15:30:45.100 (0) CLIENT IP 127.0.0.1
+21-02-24 15:30:45.101 (0) connect db demodb user dba url jdbc:cubrid:localhost:33000:demodb::: session id 12
+21-02-24 15:30:45.110 (1) prepare 0 SELECT * FROM t WHERE a = ? AND b = ?
+21-02-24 15:30:45.111 (1) prepare srv_h_id 1
+21-02-24 15:30:45.112 (1) execute srv_h_id 1 SELECT * FROM t WHERE a = ? AND b = ?
+21-02-24 15:30:45.112 (1) bind 1 : INT 42
+21-02-24 15:30:45.112 (1) bind 2 : VARCHAR (4)abc
+21-02-24 15:30:45.115 (1) execute 0 tuple 1 time 0.003
+21-02-24 15:30:45.115 (0) auto_commit
+21-02-24 15:30:45.115 (0) auto_commit 0
+21-02-24 15:30:45.115 (0) *** elapsed time 0.005
+
+21-02-24 15:30:45.116 (1) fetch srv_h_id 1
+21-02-24 15:30:45.117 (2) prepare 0 UPDATE t
+SET a = 1
+21-02-24 15:30:45.117 (2) prepare srv_h_id 2
+21-02-24 15:30:45.118 (2) execute srv_h_id 2 UPDATE t
+SET a = 1
+21-02-24 15:30:45.120 (2) execute error:-670 tuple 0 time 0.002
+21-02-24 15:30:45.121 (0) end_tran ABORT
+21-02-24 15:30:45.121 (0) end_tran 0 time 0.000
+21-02-24 15:30:45.122 (1) close_req srv_h_id 1
+"#;
+
+    #[test]
+    fn test_parse_sql_log() -> Result<()> {
+        let mut parser = SqlLogEntries::new(SQL_LOG.as_bytes()).filename("broker1_3.sql.log");
+        let entries = parser.by_ref().collect::<Result<Vec<_>>>()?;
+
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0].query_no, "1");
+        assert_eq!(entries[0].query, "SELECT * FROM t WHERE a = ? AND b = ?");
+        assert_eq!(entries[0].bind_statements, vec!["42", "abc"]);
+        assert_eq!(entries[0].bind_statements[1].bind_type, BindType::Varchar);
+        assert_eq!(entries[0].srv_h_id, Some(1));
+        assert_eq!(entries[0].source_line, Some(5));
+        assert_eq!(entries[0].filename, "broker1_3.sql.log");
+        assert_eq!(entries[0].broker_name.as_deref(), Some("broker1"));
+        assert_eq!(entries[0].cas_id, Some(3));
+        assert_eq!(entries[0].tuple_count, Some(1));
+        assert_eq!(entries[0].elapsed, Some(0.003));
+        assert_eq!(
+            LogEntry::replace_query_params(&entries[0].query, &entries[0].bind_statements)?,
+            "SELECT * FROM t WHERE a = 42 AND b = 'abc'"
+        );
+
+        assert_eq!(entries[1].query_no, "2");
+        assert_eq!(entries[1].query, "UPDATE t\nSET a = 1");
+        assert_eq!(entries[1].error_code, Some(-670));
+
+        assert!(parser.take_diagnostics().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_transactions() -> Result<()> {
+        let mut parser = SqlLogEntries::new(SQL_LOG.as_bytes()).filename("broker1_3.sql.log");
+        let entries = parser.by_ref().collect::<Result<Vec<_>>>()?;
+        let transactions = parser.take_transactions();
+
+        assert_eq!(entries[0].transaction_no, Some(1));
+        assert_eq!(entries[1].transaction_no, Some(2));
+
+        assert_eq!(transactions.len(), 2);
+        assert_eq!(transactions[0].outcome, Some(TransactionOutcome::Commit));
+        assert_eq!(transactions[0].execution_count, 1);
+        assert_eq!(transactions[0].cas_id, Some(3));
+        assert_eq!(transactions[0].elapsed(), Some(0.003));
+        assert_eq!(transactions[1].transaction_no, 2);
+        assert_eq!(transactions[1].outcome, Some(TransactionOutcome::Abort));
+        assert_eq!(transactions[1].start_time, entries[1].start_time);
+        assert_eq!(transactions[1].elapsed(), Some(0.003));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unfinished_transaction() -> Result<()> {
+        let log = "\
+21-02-24 15:30:45.112 (1) execute srv_h_id 1 INSERT INTO t VALUES (1)
+21-02-24 15:30:45.113 (1) execute 0 tuple 1 time 0.001
+21-02-24 15:30:45.114 (1) execute srv_h_id 1 INSERT INTO t VALUES (2)
+21-02-24 15:30:45.116 (1) execute 0 tuple 1 time 0.002
+";
+        let mut parser = SqlLogEntries::new(log.as_bytes());
+        let entries = parser.by_ref().collect::<Result<Vec<_>>>()?;
+        let transactions = parser.take_transactions();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].execution_count, 2);
+        assert_eq!(transactions[0].outcome, None);
+        assert_eq!(transactions[0].end_time, entries[1].end_time);
+        Ok(())
+    }
+
+    #[test]
+    fn test_execution_without_end_line() -> Result<()> {
+        let log = "21-02-24 15:30:45.112 (1) execute srv_h_id 1 SELECT 1\n\
+21-02-24 15:30:45.113 (2) execute srv_h_id 2 SELECT 2\n";
+        let entries = SqlLogEntries::new(log.as_bytes()).collect::<Result<Vec<_>>>()?;
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].query, "SELECT 1");
+        assert_eq!(entries[0].end_time, None);
+        assert_eq!(entries[1].query, "SELECT 2");
+        Ok(())
+    }
+
+    #[test]
+    fn test_multiline_bind() -> Result<()> {
+        // The second line of the value looks like an end_tran line.
+        let log = "21-02-24 15:30:45.112 (1) execute srv_h_id 1 SELECT ?\n\
+21-02-24 15:30:45.112 (1) bind 1 : VARCHAR (46)note\n\
+21-02-24 15:30:45.113 (0) end_tran ABORT\n\
+21-02-24 15:30:45.115 (1) execute 0 tuple 1 time 0.003\n\
+21-02-24 15:30:45.116 (0) end_tran COMMIT\n";
+        let mut parser = SqlLogEntries::new(log.as_bytes());
+        let entries = parser.by_ref().collect::<Result<Vec<_>>>()?;
+        let transactions = parser.take_transactions();
+
+        assert_eq!(
+            entries[0].bind_statements,
+            vec!["note\n21-02-24 15:30:45.113 (0) end_tran ABORT"]
+        );
+        assert_eq!(entries[0].elapsed, Some(0.003));
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].outcome, Some(TransactionOutcome::Commit));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unrecognized_first_line() -> Result<()> {
+        let log = "garbage\n21-02-24 15:30:45.112 (1) execute srv_h_id 1 SELECT 1\n";
+        let mut parser = SqlLogEntries::new(log.as_bytes());
+        let entries = parser.by_ref().collect::<Result<Vec<_>>>()?;
+        let diagnostics = parser.take_diagnostics();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line_no, 1);
+        assert_eq!(diagnostics[0].text, "garbage");
+        Ok(())
+    }
+}
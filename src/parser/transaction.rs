@@ -0,0 +1,52 @@
+use std::fmt;
+
+use chrono::NaiveDateTime;
+
+/// How a transaction ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionOutcome {
+    Commit,
+    Abort,
+}
+
+impl TransactionOutcome {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransactionOutcome::Commit => "COMMIT",
+            TransactionOutcome::Abort => "ABORT",
+        }
+    }
+}
+
+impl fmt::Display for TransactionOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Executions of one CAS grouped up to the `end_tran` (or auto-commit) that
+/// ends them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Transaction {
+    /// Number of the transaction within its sql.log, starting at 1. Executions
+    /// refer to it through [`LogEntry::transaction_no`](super::LogEntry::transaction_no).
+    pub transaction_no: u64,
+    pub broker_name: Option<String>,
+    pub cas_id: Option<u32>,
+    /// Start time of the first execution.
+    pub start_time: Option<NaiveDateTime>,
+    /// Time of the `end_tran` line, or the end of the last execution when
+    /// the log stops before the transaction ends.
+    pub end_time: Option<NaiveDateTime>,
+    /// `None` when the log stops before the transaction ends.
+    pub outcome: Option<TransactionOutcome>,
+    pub execution_count: u64,
+}
+
+impl Transaction {
+    /// Duration of the transaction in seconds.
+    pub fn elapsed(&self) -> Option<f64> {
+        let duration = self.end_time? - self.start_time?;
+        Some(duration.num_milliseconds() as f64 / 1000.0)
+    }
+}
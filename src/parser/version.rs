@@ -0,0 +1,78 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+
+/// `yy-mm-dd hh:mi:ss.mss`, written since CUBRID 10.
+const DATED_TIMESTAMP: &str = r"\d{2}-\d{2}-\d{2}\s\d{2}:\d{2}:\d{2}\.\d{3}";
+/// `mm/dd hh:mi:ss.mss`, written by CUBRID 9.x.
+const YEARLESS_TIMESTAMP: &str = r"\d{2}/\d{2}\s\d{2}:\d{2}:\d{2}\.\d{3}";
+
+/// CUBRID release line whose log format is expected.
+///
+/// Releases differ in the timestamp written at the start of every line and
+/// in the execute verbs they log. [`CubridVersion::Auto`] accepts the
+/// variants of every release, so that a log is read correctly without
+/// knowing which broker wrote it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CubridVersion {
+    #[default]
+    Auto,
+    /// CUBRID 9.x: `mm/dd hh:mi:ss.mss` timestamps without a year, whose
+    /// time is only known once the year is given to the parser.
+    V9,
+    /// CUBRID 10.x: `yy-mm-dd hh:mi:ss.mss` timestamps.
+    V10,
+    /// CUBRID 11.x: like 10.x, with `execute_call` and `execute_batch` lines.
+    V11,
+}
+
+impl CubridVersion {
+    /// Pattern of the timestamp at the start of a line.
+    pub fn timestamp_pattern(&self) -> String {
+        match self {
+            CubridVersion::V9 => YEARLESS_TIMESTAMP.to_string(),
+            CubridVersion::V10 | CubridVersion::V11 => DATED_TIMESTAMP.to_string(),
+            CubridVersion::Auto => format!("{}|{}", DATED_TIMESTAMP, YEARLESS_TIMESTAMP),
+        }
+    }
+
+    /// Pattern of the verb of execute lines.
+    pub fn execute_pattern(&self) -> &'static str {
+        match self {
+            CubridVersion::V9 | CubridVersion::V10 => "execute_all|execute",
+            CubridVersion::V11 | CubridVersion::Auto => {
+                "execute_all|execute_batch|execute_call|execute"
+            }
+        }
+    }
+}
+
+impl FromStr for CubridVersion {
+    type Err = anyhow::Error;
+
+    /// Accepts `auto` or a release number such as `9.3`, `10` or `11.2`.
+    fn from_str(version: &str) -> Result<Self> {
+        if version.eq_ignore_ascii_case("auto") {
+            return Ok(CubridVersion::Auto);
+        }
+        let major = version.split('.').next().unwrap_or_default();
+        match major {
+            "9" => Ok(CubridVersion::V9),
+            "10" => Ok(CubridVersion::V10),
+            "11" => Ok(CubridVersion::V11),
+            _ => Err(anyhow!("Unsupported CUBRID version: {}", version)),
+        }
+    }
+}
+
+impl fmt::Display for CubridVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CubridVersion::Auto => "auto",
+            CubridVersion::V9 => "9",
+            CubridVersion::V10 => "10",
+            CubridVersion::V11 => "11",
+        })
+    }
+}
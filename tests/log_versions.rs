@@ -0,0 +1,120 @@
+use std::fs::File;
+use std::io::BufReader;
+
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use cubrid_logtopbind_rs::parser::*;
+
+fn parse_fixture(name: &str, version: CubridVersion) -> Result<(Vec<LogEntry>, Vec<Diagnostic>)> {
+    let file = File::open(format!("testdata/versions/{}", name))?;
+    let mut parser = LogEntries::new(BufReader::new(file)).version(version);
+    let entries = parser.by_ref().collect::<Result<Vec<_>>>()?;
+    Ok((entries, parser.take_diagnostics()))
+}
+
+fn time(text: &str) -> NaiveDateTime {
+    NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.3f").unwrap()
+}
+
+/// Checks the entries shared by every fixture, which only differ in the
+/// timestamp format, the verb of the second execution and the year, if
+/// known.
+fn check_entries(entries: &[LogEntry], year: Option<i32>) {
+    assert_eq!(entries.len(), 2);
+
+    assert_eq!(
+        entries[0].query,
+        "SELECT * FROM users WHERE id = ? AND name = ?"
+    );
+    assert_eq!(entries[0].bind_statements, vec!["42", "John"]);
+    assert_eq!(
+        entries[0].start_time,
+        year.map(|year| time(&format!("{}-02-24 15:30:45.123", year)))
+    );
+    assert_eq!(entries[0].pid, Some(1));
+    assert_eq!(entries[0].tuple_count, Some(1));
+    assert_eq!(entries[0].filename, "broker1_1.sql.log");
+    assert_eq!(entries[0].cas_id, Some(1));
+
+    assert_eq!(entries[1].bind_statements, vec!["31", "42"]);
+    assert_eq!(entries[1].error_code, Some(-670));
+    assert_eq!(entries[1].elapsed, Some(0.010));
+}
+
+#[test]
+fn test_cubrid_9_3() -> Result<()> {
+    for version in [CubridVersion::V9, CubridVersion::Auto] {
+        let (entries, diagnostics) = parse_fixture("cubrid-9.3.log_top.q", version)?;
+        check_entries(&entries, None);
+        assert_eq!(entries[1].query, "UPDATE users SET age = ? WHERE id = ?");
+        assert!(diagnostics.is_empty());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_cubrid_9_3_log_year() -> Result<()> {
+    let parser = LogParser::builder().log_year(2013).build()?;
+    let file = File::open("testdata/versions/cubrid-9.3.log_top.q")?;
+    let entries = parser
+        .parse(BufReader::new(file))?
+        .collect::<Result<Vec<_>>>()?;
+
+    check_entries(&entries, Some(2013));
+    Ok(())
+}
+
+#[test]
+fn test_cubrid_10_2() -> Result<()> {
+    for version in [CubridVersion::V10, CubridVersion::Auto] {
+        let (entries, diagnostics) = parse_fixture("cubrid-10.2.log_top.q", version)?;
+        check_entries(&entries, Some(2021));
+        assert!(diagnostics.is_empty());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_cubrid_11_2() -> Result<()> {
+    for version in [CubridVersion::V11, CubridVersion::Auto] {
+        let (entries, diagnostics) = parse_fixture("cubrid-11.2.log_top.q", version)?;
+        check_entries(&entries, Some(2021));
+        assert_eq!(entries[1].query, "CALL set_age(?, ?)");
+        assert!(diagnostics.is_empty());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_crlf_line_endings() -> Result<()> {
+    let (entries, diagnostics) = parse_fixture("cubrid-10.2-crlf.log_top.q", CubridVersion::Auto)?;
+    let (lf_entries, _) = parse_fixture("cubrid-10.2.log_top.q", CubridVersion::Auto)?;
+
+    check_entries(&entries, Some(2021));
+    assert!(diagnostics.is_empty());
+    for (crlf, lf) in entries.iter().zip(&lf_entries) {
+        assert_eq!(crlf.query, lf.query);
+        assert_eq!(crlf.bind_statements, lf.bind_statements);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_version_mismatch() -> Result<()> {
+    // A 10.x parser does not recognize the year-less timestamps of 9.x.
+    let (entries, diagnostics) = parse_fixture("cubrid-9.3.log_top.q", CubridVersion::V10)?;
+
+    assert!(entries.iter().all(|entry| entry.start_time.is_none()));
+    assert!(!diagnostics.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_parse_version() -> Result<()> {
+    assert_eq!("auto".parse::<CubridVersion>()?, CubridVersion::Auto);
+    assert_eq!("9.3".parse::<CubridVersion>()?, CubridVersion::V9);
+    assert_eq!("10".parse::<CubridVersion>()?, CubridVersion::V10);
+    assert_eq!("11.2".parse::<CubridVersion>()?, CubridVersion::V11);
+    assert!("8.4".parse::<CubridVersion>().is_err());
+    Ok(())
+}
@@ -1,4 +1,5 @@
 use anyhow::Result;
+use chrono::NaiveDateTime;
 use cubrid_logtopbind_rs::parser::*;
 
 #[test]
@@ -35,6 +36,21 @@ complex-query.rs:789"#;
     assert_eq!(entries[0].query, "SELECT * FROM users WHERE id = $1");
     assert_eq!(entries[0].bind_statements, vec!["42"]);
     assert_eq!(entries[0].filename, "example.rs");
+    assert_eq!(
+        entries[0].start_time,
+        NaiveDateTime::parse_from_str("2021-02-24 15:30:45.123", "%Y-%m-%d %H:%M:%S%.3f").ok()
+    );
+    assert_eq!(
+        entries[0].end_time,
+        NaiveDateTime::parse_from_str("2021-02-24 15:30:45.125", "%Y-%m-%d %H:%M:%S%.3f").ok()
+    );
+    assert_eq!(entries[0].pid, Some(12345));
+    assert_eq!(entries[0].error_code, None);
+    assert_eq!(entries[0].tuple_count, Some(1));
+    assert_eq!(entries[0].elapsed, Some(0.123));
+    assert_eq!(entries[0].srv_h_id, Some(1));
+    assert_eq!(entries[0].source_line, Some(123));
+    assert_eq!(entries[0].broker_name, None);
 
     // Test second entry
     assert_eq!(entries[1].query_no, "2");
@@ -60,6 +76,108 @@ complex-query.rs:789"#;
     Ok(())
 }
 
+#[test]
+fn test_execute_error() -> Result<()> {
+    let log = r#"[Q1]--------------------
+21-02-24 15:30:45.123 (12345) execute srv_h_id 1 DELETE FROM users
+21-02-24 15:30:46.125 (12345) execute error:-494 tuple 0 time 1.002
+example.rs:123"#;
+
+    let entries = parse_log_entries(log)?;
+
+    assert_eq!(entries[0].error_code, Some(-494));
+    assert_eq!(entries[0].tuple_count, Some(0));
+    assert_eq!(entries[0].elapsed, Some(1.002));
+    Ok(())
+}
+
+#[test]
+fn test_cas_log_filename() -> Result<()> {
+    let log = r#"[Q1]--------------------
+21-02-24 15:30:45.123 (12345) execute srv_h_id 7 SELECT 1
+broker1_3.sql.log:4521"#;
+
+    let entries = parse_log_entries(log)?;
+
+    assert_eq!(entries[0].srv_h_id, Some(7));
+    assert_eq!(entries[0].filename, "broker1_3.sql.log");
+    assert_eq!(entries[0].source_line, Some(4521));
+    assert_eq!(entries[0].broker_name.as_deref(), Some("broker1"));
+    assert_eq!(entries[0].cas_id, Some(3));
+    Ok(())
+}
+
+#[test]
+fn test_lenient_unparseable_bind() -> Result<()> {
+    let log = r#"[Q1]--------------------
+21-02-24 15:30:45.123 (12345) execute srv_h_id 1 SELECT * FROM t WHERE a = ? AND b = ?
+21-02-24 15:30:45.124 (12345) bind 1 : RESULTSET 3
+21-02-24 15:30:45.124 (12345) bind 2 : INT 42
+example.rs:123
+[Q2]--------------------
+21-02-24 15:30:46.123 (12345) execute srv_h_id 1 SELECT 1
+example.rs:124"#;
+
+    assert!(parse_log_entries(log).is_err());
+
+    let entries = LogEntries::new(log.as_bytes())
+        .lenient(true)
+        .collect::<Result<Vec<_>>>()?;
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].bind_statements, vec!["RESULTSET 3", "42"]);
+    assert_eq!(entries[0].bind_statements[0].bind_type, BindType::Unparsed);
+    assert_eq!(
+        entries[0].parse_error.as_deref(),
+        Some("Unrecognized field format: RESULTSET 3")
+    );
+    assert_eq!(entries[1].parse_error, None);
+    Ok(())
+}
+
+#[test]
+fn test_diagnostics() -> Result<()> {
+    let log = "junk before the first block\n\
+[Q1]-----\n\
+21-02-24 15:30:45.123 (12345) execute srv_h_id 1 SELECT ?\n\
+21-02-24 15:30:45.124 (12345) bind 1 : RESULTSET 3\n\
+a.sql.log:1\n";
+
+    let mut parser = LogEntries::new(log.as_bytes()).lenient(true);
+    let entries = parser.by_ref().collect::<Result<Vec<_>>>()?;
+    let diagnostics = parser.take_diagnostics();
+
+    assert_eq!(entries.len(), 1);
+    assert_eq!(diagnostics.len(), 2);
+
+    assert_eq!(diagnostics[0].kind, DiagnosticKind::UnrecognizedLine);
+    assert_eq!(diagnostics[0].line_no, 1);
+    assert_eq!(diagnostics[0].byte_offset, 0);
+    assert_eq!(diagnostics[0].query_no, None);
+    assert_eq!(diagnostics[0].text, "junk before the first block");
+
+    assert_eq!(diagnostics[1].kind, DiagnosticKind::InvalidBind);
+    assert_eq!(diagnostics[1].line_no, 4);
+    assert_eq!(diagnostics[1].byte_offset, 96);
+    assert_eq!(diagnostics[1].query_no.as_deref(), Some("1"));
+
+    let strict_error = parse_log_entries(log).unwrap_err();
+    assert!(strict_error.to_string().starts_with("line 4: invalid_bind"));
+
+    let parallel_diagnostics = {
+        let mut parser = ParallelLogEntries::new(log.as_bytes(), 2)?.lenient(true);
+        parser.by_ref().for_each(drop);
+        parser.take_diagnostics()
+    };
+    assert_eq!(parallel_diagnostics, diagnostics);
+
+    let (entries, diagnostics) = parse_log_entries_with_diagnostics("oops\n[Q1]-----\n")?;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].text, "oops");
+    Ok(())
+}
+
 #[test]
 fn test_empty_input() -> Result<()> {
     let entries = parse_log_entries("")?;
@@ -82,10 +200,13 @@ fn test_big_line() -> Result<()> {
         .ok_or_else(|| anyhow::anyhow!("No bind statements found"))?;
 
     //println!("First bind statement: {:?}", first_bind);
-    let truncated = &first_bind[..first_bind.len().min(120)];
+    let truncated = &first_bind.value[..first_bind.value.len().min(120)];
     println!("First bind statement (truncated): {}", truncated);
 
-    let binds_10 = first_entry.bind_statements[0..10].to_vec();
+    let binds_10: Vec<&str> = first_entry.bind_statements[0..10]
+        .iter()
+        .map(|bind| bind.value.as_str())
+        .collect();
 
     println!("{}", binds_10.join("\n"));
 
@@ -101,13 +222,307 @@ Invalid line that should be ignored
 Valid line
 example.rs:123"#;
 
-    let entries = parse_log_entries(invalid_log)?;
+    let (entries, diagnostics) = parse_log_entries_with_diagnostics(invalid_log)?;
 
     assert_eq!(entries.len(), 1);
     assert_eq!(entries[0].query_no, "1");
     assert_eq!(entries[0].query, "SELECT * FROM users");
-    assert_eq!(entries[0].bind_statements, vec!["42\nValid line"]);
+    // An INT value never spans lines, so the junk after it is not glued to it.
+    assert_eq!(entries[0].bind_statements, vec!["42"]);
     assert_eq!(entries[0].filename, "example.rs");
+    let junk: Vec<_> = diagnostics.iter().map(|d| d.text.as_str()).collect();
+    assert_eq!(junk, ["Invalid line that should be ignored", "Valid line"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_streaming_entries() -> Result<()> {
+    let log = "[Q1]-----\n\
+21-02-24 15:30:45.123 (12345) execute srv_h_id 1 SELECT ?\n\
+21-02-24 15:30:45.124 (12345) bind 1 : INT 1\r\n\
+a.sql.log:1\n\
+[Q2]-----\n\
+21-02-24 15:30:45.123 (12345) execute srv_h_id 1 SELECT 2\n";
+
+    let mut entries = LogEntries::new(std::io::Cursor::new(log));
+
+    let first = entries.next().expect("first entry")?;
+    assert_eq!(first.query_no, "1");
+    assert_eq!(first.bind_statements, vec!["1"]);
+    assert_eq!(first.filename, "a.sql.log");
+
+    let second = entries.next().expect("second entry")?;
+    assert_eq!(second.query_no, "2");
+    assert_eq!(second.query, "SELECT 2");
+
+    assert!(entries.next().is_none());
+    Ok(())
+}
 
+#[test]
+fn test_parallel_matches_sequential() -> Result<()> {
+    let mut log = String::new();
+    for i in 1..=3000 {
+        log.push_str(&format!(
+            "[Q{i}]-----\n\
+21-02-24 15:30:45.123 (12345) execute srv_h_id 1 SELECT * FROM t WHERE a = ?\n\
+21-02-24 15:30:45.124 (12345) bind 1 : VARCHAR ({len})row\n\
+{i}\n\
+cas_{i}.sql.log:{i}\n\n",
+            len = format!("row\n{i}").len() + 1
+        ));
+    }
+
+    let sequential = parse_log_entries(&log)?;
+    let parallel = ParallelLogEntries::new(log.as_bytes(), 4)?.collect::<Result<Vec<_>>>()?;
+
+    assert_eq!(parallel.len(), 3000);
+    assert_eq!(parallel.len(), sequential.len());
+    for (p, s) in parallel.iter().zip(&sequential) {
+        assert_eq!(p.query_no, s.query_no);
+        assert_eq!(p.filename, s.filename);
+        assert_eq!(p.bind_statements, s.bind_statements);
+    }
+    assert_eq!(parallel[2999].bind_statements, vec!["row\n3000"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_euc_kr_log() -> Result<()> {
+    let log = "[Q1]-----\n\
+21-02-24 15:30:45.123 (1) execute srv_h_id 1 SELECT * FROM 사원 WHERE 이름 = ?\n\
+21-02-24 15:30:45.124 (1) bind 1 : VARCHAR (7)홍길동\n";
+    let (bytes, _, _) = encoding_rs::EUC_KR.encode(log);
+    assert!(std::str::from_utf8(&bytes).is_err());
+
+    for encoding in [InputEncoding::Auto, "euc-kr".parse()?] {
+        let decoding = Decoding {
+            encoding,
+            lossy: false,
+        };
+        let entries = LogEntries::new(&bytes[..])
+            .decoding(decoding)
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(entries[0].query, "SELECT * FROM 사원 WHERE 이름 = ?");
+        assert_eq!(entries[0].bind_statements, vec!["홍길동"]);
+
+        let parallel = ParallelLogEntries::new(&bytes[..], 2)?
+            .decoding(decoding)
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(parallel[0].bind_statements, entries[0].bind_statements);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_parallel_detects_encoding_once() -> Result<()> {
+    // Korean values in the first chunk, then a byte that is EUC-KR for
+    // nothing but would be taken for Windows-1252 on its own in the last
+    // chunk, which must be decoded like the first.
+    let mut log = Vec::new();
+    for i in 1..=1500 {
+        let value = if i == 1500 { "caf\u{e9}" } else { "홍길동" };
+        let encoding = if i == 1500 {
+            encoding_rs::WINDOWS_1252
+        } else {
+            encoding_rs::EUC_KR
+        };
+        let (value, _, _) = encoding.encode(value);
+        log.extend_from_slice(
+            format!(
+                "[Q{i}]-----\n\
+21-02-24 15:30:45.123 (1) execute srv_h_id 1 SELECT ?\n\
+21-02-24 15:30:45.124 (1) bind 1 : VARCHAR ({})",
+                value.len() + 1
+            )
+            .as_bytes(),
+        );
+        log.extend_from_slice(&value);
+        log.extend_from_slice(format!("\na.sql.log:{i}\n").as_bytes());
+    }
+    let decoding = Decoding {
+        encoding: InputEncoding::Auto,
+        lossy: true,
+    };
+
+    let sequential = LogEntries::new(&log[..])
+        .decoding(decoding)
+        .collect::<Result<Vec<_>>>()?;
+    let parallel = LogParser::builder()
+        .lossy(true)
+        .threads(2)
+        .build()?
+        .parse(&log[..])?
+        .collect::<Result<Vec<_>>>()?;
+
+    assert_eq!(sequential[0].bind_statements, vec!["홍길동"]);
+    assert_eq!(sequential[1499].bind_statements, vec!["caf\u{fffd}"]);
+    assert_eq!(parallel.len(), sequential.len());
+    for (p, s) in parallel.iter().zip(&sequential) {
+        assert_eq!(p.bind_statements, s.bind_statements);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_lossy_decoding() -> Result<()> {
+    let log = b"[Q1]-----\n\
+21-02-24 15:30:45.123 (1) execute srv_h_id 1 SELECT ?\n\
+21-02-24 15:30:45.124 (1) bind 1 : VARCHAR (3)a\xff\n";
+    let utf8 = Decoding {
+        encoding: "utf-8".parse()?,
+        lossy: false,
+    };
+
+    let error = LogEntries::new(&log[..])
+        .decoding(utf8)
+        .collect::<Result<Vec<_>>>()
+        .unwrap_err();
+    assert_eq!(error.to_string(), "line 3: Invalid UTF-8 data");
+
+    let mut parser = LogEntries::new(&log[..]).decoding(Decoding {
+        lossy: true,
+        ..utf8
+    });
+    let entries = parser.by_ref().collect::<Result<Vec<_>>>()?;
+    let diagnostics = parser.take_diagnostics();
+
+    assert_eq!(entries[0].bind_statements, vec!["a\u{fffd}"]);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].kind, DiagnosticKind::InvalidEncoding);
+    assert_eq!(diagnostics[0].line_no, 3);
+    Ok(())
+}
+
+#[test]
+fn test_multiline_bind_length() -> Result<()> {
+    // The value is "a\n21-02-24 ... execute 0 tuple 1 time 0.001\n[Q9]-----",
+    // whose last lines look like an end line and a block header.
+    let value = "a\n21-02-24 15:30:45.125 (1) execute 0 tuple 1 time 0.001\n[Q9]-----";
+    let log = format!(
+        "[Q1]-----\n\
+21-02-24 15:30:45.123 (1) execute srv_h_id 1 INSERT INTO t VALUES (?, ?)\n\
+21-02-24 15:30:45.124 (1) bind 1 : VARCHAR ({})\
+{}\n\
+21-02-24 15:30:45.124 (1) bind 2 : INT 7\n\
+21-02-24 15:30:45.126 (1) execute 0 tuple 1 time 0.002\n\
+broker1_1.sql.log:10\n",
+        value.len() + 1,
+        value
+    );
+
+    let (entries, diagnostics) = parse_log_entries_with_diagnostics(&log)?;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].bind_statements, vec![value, "7"]);
+    assert!(!entries[0].bind_statements[0].truncated);
+    assert_eq!(entries[0].elapsed, Some(0.002));
+    assert!(diagnostics.is_empty());
+
+    let parallel = ParallelLogEntries::new(log.as_bytes(), 2)?.collect::<Result<Vec<_>>>()?;
+    assert_eq!(parallel[0].bind_statements, entries[0].bind_statements);
+    Ok(())
+}
+
+#[test]
+fn test_parallel_multiline_bind_at_chunk_end() -> Result<()> {
+    // The value of the last block of the first chunk holds a block header,
+    // where the input must not be split.
+    let mut log = String::new();
+    for i in 1..=1023 {
+        log.push_str(&format!(
+            "[Q{i}]-----\n\
+21-02-24 15:30:45.123 (1) execute srv_h_id 1 SELECT ?\n\
+21-02-24 15:30:45.124 (1) bind 1 : INT {i}\n"
+        ));
+    }
+    log.push_str(
+        "[Q1024]-----\n\
+21-02-24 15:30:45.123 (1) execute srv_h_id 1 SELECT ?\n\
+21-02-24 15:30:45.124 (1) bind 1 : VARCHAR (14)a\n[Q9]-----\nb\n\
+21-02-24 15:30:45.126 (1) execute 0 tuple 1 time 0.002\n\
+[Q1025]-----\n\
+21-02-24 15:30:45.127 (1) execute srv_h_id 1 SELECT 1\n",
+    );
+
+    let (sequential, diagnostics) = parse_log_entries_with_diagnostics(&log)?;
+    let mut parser = ParallelLogEntries::new(log.as_bytes(), 2)?;
+    let parallel = parser.by_ref().collect::<Result<Vec<_>>>()?;
+
+    assert_eq!(sequential.len(), 1025);
+    assert_eq!(sequential[1023].bind_statements, vec!["a\n[Q9]-----\nb"]);
+    assert_eq!(sequential[1023].elapsed, Some(0.002));
+    assert!(diagnostics.is_empty());
+    assert_eq!(parallel.len(), sequential.len());
+    for (p, s) in parallel.iter().zip(&sequential) {
+        assert_eq!(p.query_no, s.query_no);
+        assert_eq!(p.bind_statements, s.bind_statements);
+        assert_eq!(p.elapsed, s.elapsed);
+    }
+    assert!(parser.take_diagnostics().is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_multiline_bind_euc_kr() -> Result<()> {
+    // 홍길동 and 이몽룡 are 6 bytes each in EUC-KR: 13 bytes with the line
+    // break, 14 with the NUL.
+    let log = "[Q1]-----\n\
+21-02-24 15:30:45.123 (1) execute srv_h_id 1 SELECT ?\n\
+21-02-24 15:30:45.124 (1) bind 1 : VARCHAR (14)홍길동\n\
+이몽룡\n\
+a.sql.log:1\n";
+    let (bytes, _, _) = encoding_rs::EUC_KR.encode(log);
+
+    let entries = LogEntries::new(&bytes[..])
+        .decoding(Decoding {
+            encoding: "euc-kr".parse()?,
+            lossy: false,
+        })
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(entries[0].bind_statements, vec!["홍길동\n이몽룡"]);
+    assert!(!entries[0].bind_statements[0].truncated);
+    assert_eq!(entries[0].filename, "a.sql.log");
+    Ok(())
+}
+
+#[test]
+fn test_truncated_bind() -> Result<()> {
+    // The first value declares 100 bytes but the log goes on after 9 of them.
+    let log = "[Q1]-----\n\
+21-02-24 15:30:45.123 (1) execute srv_h_id 1 SELECT ?, ?\n\
+21-02-24 15:30:45.124 (1) bind 1 : VARCHAR (101)first\n\
+line\n\
+21-02-24 15:30:45.124 (1) bind 2 : VARCHAR (4)abc\n\
+21-02-24 15:30:45.126 (1) execute 0 tuple 1 time 0.002\n\
+a.sql.log:1\n\
+[Q2]-----\n\
+21-02-24 15:30:46.123 (1) execute srv_h_id 1 SELECT ?\n\
+21-02-24 15:30:46.124 (1) bind 1 : CHAR (50)cut at the end";
+
+    let (entries, diagnostics) = parse_log_entries_with_diagnostics(log)?;
+    assert_eq!(entries.len(), 2);
+
+    assert_eq!(entries[0].bind_statements, vec!["first\nline", "abc"]);
+    assert!(entries[0].bind_statements[0].truncated);
+    assert!(!entries[0].bind_statements[1].truncated);
+    assert_eq!(entries[0].elapsed, Some(0.002));
+    assert_eq!(entries[0].filename, "a.sql.log");
+
+    assert_eq!(entries[1].bind_statements, vec!["cut at the end"]);
+    assert!(entries[1].bind_statements[0].truncated);
+
+    assert_eq!(diagnostics.len(), 2);
+    assert!(diagnostics
+        .iter()
+        .all(|d| d.kind == DiagnosticKind::TruncatedBind));
+    assert_eq!(diagnostics[0].line_no, 3);
+    assert_eq!(diagnostics[0].query_no.as_deref(), Some("1"));
+    assert_eq!(
+        diagnostics[0].message,
+        "Value truncated to 10 of its 100 bytes"
+    );
+    assert_eq!(diagnostics[1].line_no, 10);
     Ok(())
 }
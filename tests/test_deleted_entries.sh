@@ -5,6 +5,22 @@ set -euo pipefail
 # This script runs the 'logtopbind' binary in release mode, compares the generated
 # log file 'deleted_entries.log' against the expected output in 'testdata/deleted_entries.txt',
 # and cleans up the log file after the test.
+#
+# The expected output needs the capture 'testdata/log_top_50m.q', which is too
+# large to be kept in the repository. It was recorded when these entries were
+# still dropped instead of kept, and has not been regenerated
+# since: placeholders are now counted outside literals and comments, numbered
+# and named placeholders are bound, and batch executions split into parameter
+# sets are no longer mismatches, so the query numbers listed may differ. With
+# the capture at hand, regenerate it with:
+#
+#   cargo run --release --bin logtopbind ./testdata/log_top_50m.q
+#   cp deleted_entries.log ./testdata/deleted_entries.txt
+
+if [ ! -f ./testdata/log_top_50m.q ]; then
+    echo "Error: './testdata/log_top_50m.q' is missing; copy the capture there first."
+    exit 1
+fi
 
 # Run the binary with the provided test data.
 echo "Running cargo command..."